@@ -0,0 +1,133 @@
+// Text charts drawn with Unicode block characters.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Days, NaiveDate};
+use unicode_width::UnicodeWidthStr;
+
+const BAR_EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const SPARK: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const HEAT: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+const MAX_LABEL_WIDTH: usize = 16;
+
+/// Width to draw in: the terminal's, then `$COLUMNS`, then 80.
+pub fn width() -> usize {
+    if let Some((terminal_size::Width(w), _)) = terminal_size::terminal_size() {
+        return w as usize;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c: &usize| c > 0)
+        .unwrap_or(80)
+}
+
+// Pads or truncates `s` to exactly `w` display columns.
+fn fit(s: &str, w: usize) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        let next = format!("{}{}", out, c);
+        if next.width() > w {
+            break;
+        }
+        out = next;
+    }
+    let pad = w - out.width();
+    out + &" ".repeat(pad)
+}
+
+/// One horizontal bar per row, scaled to the largest value.
+pub fn bars(rows: &[(String, i64)], width: usize) -> Vec<String> {
+    let label_w = rows.iter().map(|(l, _)| l.width()).max().unwrap_or(0).min(MAX_LABEL_WIDTH);
+    let value_w = rows.iter().map(|(_, v)| v.to_string().len()).max().unwrap_or(0);
+    let bar_w = width.saturating_sub(label_w + value_w + 3).max(10);
+    let max = rows.iter().map(|(_, v)| *v).max().unwrap_or(0).max(1);
+
+    rows.iter()
+        .map(|(label, v)| {
+            let eighths = (*v).max(0) as u128 * bar_w as u128 * 8 / max as u128;
+            let mut bar = "█".repeat((eighths / 8) as usize);
+            let partial = (eighths % 8) as usize;
+            if partial > 0 {
+                bar.push(BAR_EIGHTHS[partial - 1]);
+            }
+            format!("{} {} {:>vw$}", fit(label, label_w), fit(&bar, bar_w), v, vw = value_w)
+        })
+        .collect()
+}
+
+/// One block per value, scaled between the smallest and largest value.
+pub fn sparkline(values: &[i64]) -> String {
+    let (Some(&min), Some(&max)) = (values.iter().min(), values.iter().max()) else {
+        return String::new();
+    };
+    values
+        .iter()
+        .map(|&v| {
+            if max == min {
+                SPARK[0]
+            } else {
+                SPARK[((v - min) as i128 * 7 / (max - min) as i128) as usize]
+            }
+        })
+        .collect()
+}
+
+/// GitHub-style calendar: one column per week, one row per weekday, darker
+/// cells for more spending. Only the most recent weeks that fit are drawn.
+pub fn heatmap(daily: &BTreeMap<NaiveDate, i64>, from: NaiveDate, to: NaiveDate, width: usize) -> Vec<String> {
+    const LABEL_W: usize = 4;
+    let monday = |d: NaiveDate| d - Days::new(d.weekday().num_days_from_monday() as u64);
+    let weeks = ((monday(to) - monday(from)).num_days() / 7 + 1) as usize;
+    let shown = weeks.min(width.saturating_sub(LABEL_W).max(1));
+    let first_week = monday(to) - Days::new(7 * (shown as u64 - 1));
+
+    // Levels split the non-zero days into quartiles.
+    let mut amounts: Vec<i64> = daily
+        .iter()
+        .filter(|(d, v)| **d >= from && **d <= to && **v > 0)
+        .map(|(_, v)| *v)
+        .collect();
+    amounts.sort_unstable();
+    let quartile = |q: usize| amounts.get((amounts.len() * q / 4).min(amounts.len().saturating_sub(1))).copied();
+    let cuts = [quartile(1), quartile(2), quartile(3)];
+    let level = |v: i64| {
+        if v <= 0 {
+            HEAT[0]
+        } else {
+            HEAT[1 + cuts.iter().filter(|c| c.is_some_and(|c| v > c)).count()]
+        }
+    };
+
+    // Month names sit above the week the month starts in, when there is room.
+    let mut header = vec![' '; shown + 3];
+    let mut prev_month = None;
+    for w in 0..shown {
+        let first_day = (first_week + Days::new(7 * w as u64)).max(from);
+        if prev_month != Some(first_day.month()) && header[w.saturating_sub(1)..w + 3].iter().all(|c| *c == ' ') {
+            for (i, c) in first_day.format("%b").to_string().chars().take(3).enumerate() {
+                header[w + i] = c;
+            }
+        }
+        prev_month = Some(first_day.month());
+    }
+    let header = format!("{}{}", " ".repeat(LABEL_W), header.iter().collect::<String>().trim_end());
+
+    let mut lines = vec![header];
+    for (row, name) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().enumerate() {
+        let mut line = format!("{:<w$}", name, w = LABEL_W);
+        for w in 0..shown {
+            let d = first_week + Days::new((7 * w + row) as u64);
+            if d < from || d > to {
+                line.push(' ');
+            } else {
+                line.push(level(daily.get(&d).copied().unwrap_or(0)));
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    let legend: Vec<String> = HEAT.iter().map(|c| c.to_string()).collect();
+    lines.push(format!("{:<w$}less {} more", "", legend.join(""), w = LABEL_W));
+    lines
+}
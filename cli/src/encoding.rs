@@ -0,0 +1,221 @@
+// Character encodings for CSV files. "Shift_JIS" is the Windows variant
+// (CP932) that Japanese Excel and bank downloads actually use; encoding_rs
+// implements it under that name.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use encoding_rs::{EncoderResult, SHIFT_JIS};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TextEncoding {
+    Utf8,
+    Utf8Bom,
+    ShiftJis,
+}
+
+pub const ENCODING_NAMES: &str = "utf-8|utf-8-bom|shift_jis (cp932)";
+
+impl FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "utf-8-bom" | "utf8-bom" | "utf-8-sig" => Ok(TextEncoding::Utf8Bom),
+            "shift_jis" | "shift-jis" | "sjis" | "cp932" | "windows-31j" => Ok(TextEncoding::ShiftJis),
+            _ => Err(format!("Unknown encoding: {} (expected {})", s, ENCODING_NAMES)),
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf8Bom => "UTF-8 (BOM)",
+            TextEncoding::ShiftJis => "Shift_JIS",
+        })
+    }
+}
+
+impl TextEncoding {
+    /// UTF-8 if `bytes` are valid UTF-8, otherwise Shift_JIS.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if std::str::from_utf8(bytes).is_ok() { TextEncoding::Utf8 } else { TextEncoding::ShiftJis }
+    }
+
+    /// Bytes to write at the start of a file.
+    pub fn bom(self) -> &'static [u8] {
+        if self == TextEncoding::Utf8Bom { UTF8_BOM } else { b"" }
+    }
+
+    /// Encodes `s`. A character the encoding cannot represent is returned as
+    /// the error, or written as `?` when `lossy` is set.
+    pub fn encode(self, s: &str, lossy: bool) -> Result<Cow<'_, [u8]>, char> {
+        if self != TextEncoding::ShiftJis || s.is_ascii() {
+            return Ok(Cow::Borrowed(s.as_bytes()));
+        }
+        let mut encoder = SHIFT_JIS.new_encoder();
+        let mut out = Vec::with_capacity(s.len() + 8);
+        let mut rest = s;
+        loop {
+            let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut out, true);
+            rest = &rest[read..];
+            match result {
+                EncoderResult::InputEmpty => return Ok(Cow::Owned(out)),
+                EncoderResult::OutputFull => out.reserve(rest.len() * 2 + 8),
+                EncoderResult::Unmappable(c) => match cp932_variant(c) {
+                    Some(alt) => {
+                        out.reserve(2);
+                        let alt = alt.encode_utf8(&mut [0; 4]).to_string();
+                        // Every variant is in CP932, so this always consumes `alt`.
+                        let _ = encoder.encode_from_utf8_to_vec_without_replacement(&alt, &mut out, false);
+                    }
+                    None if lossy => out.push(b'?'),
+                    None => return Err(c),
+                },
+            }
+        }
+    }
+
+    /// Decodes a whole file. A leading UTF-8 BOM is skipped, so `utf-8` reads
+    /// files written with `utf-8-bom` and vice versa. Invalid input is an error
+    /// naming the first bad line rather than being replaced silently.
+    pub fn decode(self, bytes: &[u8]) -> Result<String, String> {
+        let bad_line = |valid: &str| valid.matches('\n').count() + 1;
+        match self {
+            TextEncoding::Utf8 | TextEncoding::Utf8Bom => {
+                let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+                match std::str::from_utf8(bytes) {
+                    Ok(s) => Ok(s.to_string()),
+                    Err(e) => {
+                        let valid = std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default();
+                        Err(format!(
+                            "Line {} is not valid UTF-8 (try --encoding shift_jis)",
+                            bad_line(valid)
+                        ))
+                    }
+                }
+            }
+            TextEncoding::ShiftJis => {
+                let (text, had_errors) = SHIFT_JIS.decode_without_bom_handling(bytes);
+                if had_errors {
+                    let at = text.find('\u{FFFD}').unwrap_or(0);
+                    return Err(format!(
+                        "Line {} is not valid Shift_JIS (try --encoding utf-8)",
+                        bad_line(&text[..at])
+                    ));
+                }
+                Ok(text.into_owned())
+            }
+        }
+    }
+}
+
+// Text typed on macOS or copied from JIS-based sources uses code points that
+// CP932 spells differently; they show the same glyph, so write CP932's.
+fn cp932_variant(c: char) -> Option<char> {
+    Some(match c {
+        '\u{301C}' => '\u{FF5E}', // 〜 wave dash
+        '\u{2016}' => '\u{2225}', // ‖ double vertical line
+        '\u{2212}' => '\u{FF0D}', // − minus sign
+        '\u{2014}' => '\u{2015}', // — em dash
+        '\u{00A2}' => '\u{FFE0}', // ¢
+        '\u{00A3}' => '\u{FFE1}', // £
+        '\u{00AC}' => '\u{FFE2}', // ¬
+        _ => return None,
+    })
+}
+
+/// Encodes one CSV record. `what` names the record in the error message.
+pub fn encode_record<'a>(
+    fields: &'a [String],
+    encoding: TextEncoding,
+    lossy: bool,
+    what: &str,
+) -> Result<Vec<Cow<'a, [u8]>>, String> {
+    fields
+        .iter()
+        .map(|f| {
+            encoding.encode(f, lossy).map_err(|c| {
+                format!(
+                    "{}: '{}' (U+{:04X}) cannot be written as {}; change it or pass --lossy to write '?'",
+                    what, c, c as u32, encoding
+                )
+            })
+        })
+        .collect()
+}
+
+/// Removes `--encoding E` and `--lossy` from `args`, returning them and the rest.
+pub fn take_encoding_args(args: &[String]) -> Result<(Option<TextEncoding>, bool, Vec<String>), String> {
+    let mut encoding = None;
+    let mut lossy = false;
+    let mut rest = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--encoding" => {
+                let value = it.next().ok_or("Missing value for --encoding")?;
+                encoding = Some(value.parse()?);
+            }
+            "--lossy" => lossy = true,
+            _ => rest.push(a.clone()),
+        }
+    }
+    Ok((encoding, lossy, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sjis(s: &str) -> Vec<u8> {
+        TextEncoding::ShiftJis.encode(s, false).unwrap().into_owned()
+    }
+
+    #[test]
+    fn shift_jis_round_trips_japanese_text() {
+        let text = "食費,コンビニ弁当とお茶\n交通費,Suica チャージ\n";
+        let bytes = sjis(text);
+        assert!(std::str::from_utf8(&bytes).is_err());
+        assert_eq!(TextEncoding::detect(&bytes), TextEncoding::ShiftJis);
+        assert_eq!(TextEncoding::ShiftJis.decode(&bytes).unwrap(), text);
+    }
+
+    #[test]
+    fn cp932_only_characters_are_written() {
+        // ① is an NEC special character and ～ (U+FF5E) the fullwidth tilde;
+        // neither is in JIS X 0208.
+        assert_eq!(sjis("①"), b"\x87\x40");
+        assert_eq!(sjis("～"), b"\x81\x60");
+        assert_eq!(TextEncoding::ShiftJis.decode(&sjis("①～③")).unwrap(), "①～③");
+        // The JIS wave dash is written as CP932's fullwidth tilde.
+        assert_eq!(sjis("10〜20"), b"10\x81\x6020");
+    }
+
+    #[test]
+    fn unmappable_characters_fail_unless_lossy() {
+        assert_eq!(TextEncoding::ShiftJis.encode("カフェ☕代", false), Err('☕'));
+        let lossy = TextEncoding::ShiftJis.encode("カフェ☕代", true).unwrap();
+        assert_eq!(TextEncoding::ShiftJis.decode(&lossy).unwrap(), "カフェ?代");
+        let fields = vec!["ok".to_string(), "☕".to_string()];
+        let err = encode_record(&fields, TextEncoding::ShiftJis, false, "Entry #3").unwrap_err();
+        assert!(err.starts_with("Entry #3: '☕' (U+2615)"), "{}", err);
+    }
+
+    #[test]
+    fn utf8_bom_is_skipped_on_input() {
+        let bytes = [UTF8_BOM, "日付,金額\n".as_bytes()].concat();
+        assert_eq!(TextEncoding::detect(&bytes), TextEncoding::Utf8);
+        assert_eq!(TextEncoding::Utf8.decode(&bytes).unwrap(), "日付,金額\n");
+        assert_eq!(TextEncoding::Utf8Bom.decode(&bytes).unwrap(), "日付,金額\n");
+        assert_eq!(TextEncoding::Utf8Bom.decode("日付\n".as_bytes()).unwrap(), "日付\n");
+        assert_eq!(TextEncoding::Utf8Bom.bom(), UTF8_BOM);
+        assert_eq!(TextEncoding::Utf8.bom(), b"");
+    }
+}
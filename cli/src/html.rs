@@ -0,0 +1,287 @@
+// Standalone HTML report: one file with inline CSS and SVG, no network assets.
+
+use std::f64::consts::PI;
+
+use ledger_module::{CategoryTotal, Entry, Kind};
+
+use crate::fmt_amount as amount;
+
+const TEMPLATE: &str = include_str!("../templates/report.html");
+
+const PALETTE: [&str; 8] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+];
+const OTHER_COLOR: &str = "#bab0ac";
+pub const INCOME_COLOR: &str = "#59a14f";
+pub const EXPENSE_COLOR: &str = "#e15759";
+
+pub struct BarSeries {
+    pub name: &'static str,
+    pub color: &'static str,
+    pub values: Vec<i64>,
+}
+
+pub struct Report<'a> {
+    pub title: String,
+    pub income: i64,
+    pub expense: i64,
+    pub balance: i64,
+    pub expense_categories: &'a [CategoryTotal],
+    pub income_categories: &'a [CategoryTotal],
+    pub bar_title: String,
+    pub bar_labels: Vec<String>,
+    pub bar_series: Vec<BarSeries>,
+    pub entries: &'a [Entry],
+}
+
+pub fn render(r: &Report) -> String {
+    let values = [
+        ("title", escape(&r.title)),
+        ("generated", chrono::Local::now().format("%Y-%m-%d %H:%M").to_string()),
+        ("income", amount(r.income)),
+        ("expense", amount(r.expense)),
+        ("balance", amount(r.balance)),
+        ("pie", pie_svg("Expense by category", r.expense_categories)),
+        ("bars", bar_svg(&r.bar_title, &r.bar_labels, &r.bar_series)),
+        ("expense_table", category_table(r.expense_categories, true)),
+        ("income_table", category_table(r.income_categories, false)),
+        ("entries", entry_table(r.entries)),
+    ];
+    fill(TEMPLATE, &values)
+}
+
+// Replaces every `{{name}}` in one pass, so values are never re-scanned.
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len() * 2);
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 2..];
+        match after.find("}}") {
+            Some(close) => {
+                let key = &after[..close];
+                match values.iter().find(|(k, _)| *k == key) {
+                    Some((_, v)) => out.push_str(v),
+                    None => out.push_str(&rest[open..open + close + 4]),
+                }
+                rest = &after[close + 2..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// The largest categories get their own colour; the rest are merged into "Other".
+fn slices(rows: &[CategoryTotal]) -> Vec<(String, i64, &'static str)> {
+    let mut v: Vec<(String, i64, &'static str)> = rows
+        .iter()
+        .take(PALETTE.len() - 1)
+        .zip(PALETTE)
+        .map(|(r, c)| (r.category.clone(), r.total, c))
+        .collect();
+    let rest: i64 = rows.iter().skip(PALETTE.len() - 1).map(|r| r.total).sum();
+    if rows.len() == PALETTE.len() {
+        let last = &rows[PALETTE.len() - 1];
+        v.push((last.category.clone(), last.total, PALETTE[PALETTE.len() - 1]));
+    } else if rest > 0 {
+        v.push(("Other".to_string(), rest, OTHER_COLOR));
+    }
+    v
+}
+
+fn pie_svg(title: &str, rows: &[CategoryTotal]) -> String {
+    let total: i64 = rows.iter().map(|r| r.total).sum();
+    if total <= 0 {
+        return format!("<div><h3>{}</h3><p class=\"empty\">(no data)</p></div>", escape(title));
+    }
+    let slices = slices(rows);
+    let (cx, cy, r) = (110.0, 110.0, 100.0);
+    let height = 220.max(20 + 22 * slices.len());
+    let mut svg = format!(
+        "<div><h3>{}</h3>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"440\" height=\"{}\" role=\"img\">\n",
+        escape(title),
+        height
+    );
+    let mut angle = -PI / 2.0;
+    for (i, (name, value, color)) in slices.iter().enumerate() {
+        let share = *value as f64 / total as f64;
+        if share >= 0.9999 {
+            svg.push_str(&format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n", cx, cy, r, color));
+        } else if share > 0.0 {
+            let end = angle + share * 2.0 * PI;
+            svg.push_str(&format!(
+                "<path d=\"M{cx},{cy} L{:.2},{:.2} A{r},{r} 0 {} 1 {:.2},{:.2} Z\" fill=\"{}\" stroke=\"#fff\" stroke-width=\"1\"/>\n",
+                cx + r * angle.cos(),
+                cy + r * angle.sin(),
+                if share > 0.5 { 1 } else { 0 },
+                cx + r * end.cos(),
+                cy + r * end.sin(),
+                color,
+            ));
+            angle = end;
+        }
+        let y = 20 + 22 * i;
+        svg.push_str(&format!(
+            "<rect x=\"240\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/><text x=\"258\" y=\"{}\" font-size=\"12\">{} {:.1}%</text>\n",
+            y,
+            color,
+            y + 11,
+            escape(name),
+            share * 100.0
+        ));
+    }
+    svg.push_str("</svg></div>");
+    svg
+}
+
+fn bar_svg(title: &str, labels: &[String], series: &[BarSeries]) -> String {
+    let max = series.iter().flat_map(|s| s.values.iter().copied()).max().unwrap_or(0);
+    if labels.is_empty() || max <= 0 {
+        return format!("<div><h3>{}</h3><p class=\"empty\">(no data)</p></div>", escape(title));
+    }
+    let (width, height, left, bottom, top) = (560.0, 240.0, 60.0, 30.0, 10.0);
+    let plot_w = width - left - 10.0;
+    let plot_h = height - bottom - top;
+    let group_w = plot_w / labels.len() as f64;
+    let bar_w = (group_w * 0.8 / series.len() as f64).max(1.0);
+    // Label at most about 16 groups so the text does not overlap.
+    let label_every = labels.len().div_ceil(16);
+
+    let mut svg = format!(
+        "<div><h3>{}</h3>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" role=\"img\">\n",
+        escape(title),
+        width,
+        height + 20.0
+    );
+    for step in 0..=4 {
+        let v = max as f64 * step as f64 / 4.0;
+        let y = top + plot_h - plot_h * step as f64 / 4.0;
+        svg.push_str(&format!(
+            "<line x1=\"{left}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"#eee\"/><text x=\"{:.1}\" y=\"{:.1}\" font-size=\"10\" text-anchor=\"end\">{}</text>\n",
+            width - 10.0,
+            left - 4.0,
+            y + 3.0,
+            amount(v.round() as i64)
+        ));
+    }
+    for (i, label) in labels.iter().enumerate() {
+        let x0 = left + group_w * i as f64 + group_w * 0.1;
+        for (j, s) in series.iter().enumerate() {
+            let v = s.values.get(i).copied().unwrap_or(0).max(0);
+            let h = plot_h * v as f64 / max as f64;
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{} {}: {}</title></rect>\n",
+                x0 + bar_w * j as f64,
+                top + plot_h - h,
+                bar_w,
+                h,
+                s.color,
+                escape(label),
+                s.name,
+                amount(v)
+            ));
+        }
+        if i.is_multiple_of(label_every) {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n",
+                left + group_w * (i as f64 + 0.5),
+                top + plot_h + 14.0,
+                escape(label)
+            ));
+        }
+    }
+    for (j, s) in series.iter().enumerate() {
+        let x = left + 110.0 * j as f64;
+        svg.push_str(&format!(
+            "<rect x=\"{x}\" y=\"{:.1}\" width=\"12\" height=\"12\" fill=\"{}\"/><text x=\"{:.1}\" y=\"{:.1}\" font-size=\"12\">{}</text>\n",
+            height + 4.0,
+            s.color,
+            x + 16.0,
+            height + 15.0,
+            s.name
+        ));
+    }
+    svg.push_str("</svg></div>");
+    svg
+}
+
+fn category_table(rows: &[CategoryTotal], with_colors: bool) -> String {
+    if rows.is_empty() {
+        return "<p class=\"empty\">(no data)</p>".to_string();
+    }
+    let total: i64 = rows.iter().map(|r| r.total).sum();
+    let colors: Vec<&str> = if with_colors {
+        let s = slices(rows);
+        rows.iter()
+            .map(|r| s.iter().find(|(n, _, _)| *n == r.category).map(|(_, _, c)| *c).unwrap_or(OTHER_COLOR))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let mut html = String::from(
+        "<table>\n<tr><th>Category</th><th class=\"num\">Amount</th><th class=\"num\">Share</th></tr>\n",
+    );
+    for (i, r) in rows.iter().enumerate() {
+        let swatch = colors
+            .get(i)
+            .map(|c| format!("<span class=\"swatch\" style=\"background:{}\"></span>", c))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td>{}{}</td><td class=\"num\">{}</td><td class=\"num\">{:.1}%</td></tr>\n",
+            swatch,
+            escape(&r.category),
+            amount(r.total),
+            r.total as f64 * 100.0 / total as f64
+        ));
+    }
+    html.push_str(&format!(
+        "<tr><th>Total</th><th class=\"num\">{}</th><th></th></tr>\n</table>",
+        amount(total)
+    ));
+    html
+}
+
+fn entry_table(entries: &[Entry]) -> String {
+    if entries.is_empty() {
+        return "<p class=\"empty\">(no entries)</p>".to_string();
+    }
+    let mut html = String::from(
+        "<table>\n<tr><th>Date</th><th>Kind</th><th>Category</th><th>Note</th><th>Account</th><th>Tags</th><th class=\"num\">Amount</th></tr>\n",
+    );
+    for e in entries {
+        let (kind, class) = if e.kind == Kind::Expense { ("Expense", "expense") } else { ("Income", "income") };
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num {}\">{}</td></tr>\n",
+            e.created_at.format("%Y-%m-%d %H:%M"),
+            kind,
+            escape(&e.category),
+            escape(e.note.as_deref().unwrap_or("")),
+            escape(e.account.as_deref().unwrap_or("")),
+            escape(&e.tags.join(", ")),
+            class,
+            amount(e.amount)
+        ));
+    }
+    html.push_str("</table>");
+    html
+}
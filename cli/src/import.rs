@@ -0,0 +1,213 @@
+// Reads entries from CSV: the layout written by `export csv`, and the exports
+// of Japanese household-budget apps.
+
+mod camt;
+mod moneyforward;
+mod mt940;
+mod zaim;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::{ReaderBuilder, StringRecord};
+use ledger_module::{ImportItem, Kind, NewEntry};
+
+pub use camt::read_camt053;
+pub use moneyforward::read_moneyforward;
+pub use mt940::read_mt940;
+pub use zaim::read_zaim;
+
+const DATETIME_FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y/%m/%d %H:%M:%S", "%Y/%m/%d %H:%M"];
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d", "%Y/%m/%d"];
+
+/// Parses `text` into entries. Columns are matched by header name: `kind`,
+/// `amount` and `category` are required; `note`, `created_at`, `account` and
+/// `tags` are optional and `id` is ignored. Returns every problem found, with
+/// line numbers, instead of a partial result.
+pub fn read_csv(text: &str) -> Result<Vec<NewEntry>, Vec<String>> {
+    let mut rdr = ReaderBuilder::new().flexible(true).from_reader(text.as_bytes());
+    let headers = rdr.headers().map_err(|e| vec![e.to_string()])?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let (Some(kind_col), Some(amount_col), Some(category_col)) = (column("kind"), column("amount"), column("category"))
+    else {
+        return Err(vec!["Header must contain kind, amount and category columns".to_string()]);
+    };
+    let (note_col, date_col, account_col, tags_col) =
+        (column("note"), column("created_at"), column("account"), column("tags"));
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for record in rdr.records() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                errors.push(e.to_string());
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let field = |col: Option<usize>| col.and_then(|c| record.get(c)).map(str::trim).unwrap_or("");
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+
+        let kind = match field(Some(kind_col)).to_ascii_lowercase().as_str() {
+            "expense" => Kind::Expense,
+            "income" => Kind::Income,
+            other => {
+                errors.push(format!("line {}: invalid kind '{}'", line, other));
+                continue;
+            }
+        };
+        let amount = match field(Some(amount_col)).replace(',', "").parse::<i64>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                errors.push(format!("line {}: invalid amount '{}'", line, field(Some(amount_col))));
+                continue;
+            }
+        };
+        let category = field(Some(category_col));
+        if category.is_empty() {
+            errors.push(format!("line {}: missing category", line));
+            continue;
+        }
+        let mut entry = NewEntry::new(kind, amount, category);
+        let date = field(date_col);
+        if !date.is_empty() {
+            match parse_datetime(date) {
+                Some(at) => entry.created_at = Some(at),
+                None => {
+                    errors.push(format!("line {}: invalid date '{}'", line, date));
+                    continue;
+                }
+            }
+        }
+        let note = field(note_col);
+        entry.note = (!note.is_empty()).then(|| note.to_string());
+        let account = field(account_col);
+        entry.account = (!account.is_empty()).then(|| account.to_string());
+        entry.tags = field(tags_col)
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        entries.push(entry);
+    }
+    if errors.is_empty() { Ok(entries) } else { Err(errors) }
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(s, f).ok())
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Where the second level of an app's category hierarchy goes.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Subcategory {
+    /// Category is the top level; the subcategory becomes a tag.
+    Tag,
+    /// Category is `top/sub`.
+    Join,
+}
+
+/// Rows read from an app export. Transfers between the user's own accounts
+/// and rows the app leaves out of its totals are counted, not imported.
+pub struct AppImport {
+    pub items: Vec<ImportItem>,
+    pub transfers: usize,
+    pub excluded: usize,
+}
+
+/// Settings for bank statement formats, which carry no categories.
+pub struct BankOptions {
+    pub category: String,
+    /// Overrides the account number given in the statement.
+    pub account: Option<String>,
+    /// Round amounts with cents to whole units instead of rejecting them.
+    pub round: bool,
+}
+
+// Placeholders the apps use for "no category".
+const UNCATEGORIZED: [&str; 3] = ["", "-", "未分類"];
+
+pub(crate) fn categorize(entry: &mut NewEntry, top: &str, sub: &str, mode: Subcategory) {
+    let top = if UNCATEGORIZED.contains(&top) { "未分類" } else { top };
+    entry.category = top.to_string();
+    if UNCATEGORIZED.contains(&sub) || sub == top {
+        return;
+    }
+    match mode {
+        Subcategory::Tag => entry.tags.push(sub.to_string()),
+        Subcategory::Join => entry.category = format!("{}/{}", top, sub),
+    }
+}
+
+// Non-empty values joined with " / ".
+pub(crate) fn join_note(parts: &[&str]) -> Option<String> {
+    let parts: Vec<&str> = parts.iter().copied().filter(|p| !p.is_empty()).collect();
+    (!parts.is_empty()).then(|| parts.join(" / "))
+}
+
+// Amounts such as "1,200", "-3,000" or "¥500".
+fn parse_amount(s: &str) -> Option<i64> {
+    let digits: String = s.chars().filter(|c| !matches!(c, ',' | '¥' | '￥' | '円' | ' ')).collect();
+    if digits.is_empty() { Some(0) } else { digits.parse().ok() }
+}
+
+/// A decimal amount in whole units. `decimal` is the decimal separator; the
+/// other of `.` and `,` is taken as a thousands separator. A non-zero fraction
+/// is rounded half away from zero when `round`, otherwise rejected.
+pub(crate) fn whole_units(s: &str, decimal: char, round: bool) -> Option<i64> {
+    let thousands = if decimal == '.' { ',' } else { '.' };
+    let s: String = s.trim().chars().filter(|c| *c != thousands).collect();
+    let (whole, fraction) = s.split_once(decimal).unwrap_or((&s, ""));
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let negative = whole.starts_with('-');
+    let mut units: i64 = if whole.is_empty() || whole == "-" { 0 } else { whole.parse().ok()? };
+    if fraction.chars().any(|c| c != '0') {
+        if !round {
+            return None;
+        }
+        if fraction.starts_with(['5', '6', '7', '8', '9']) {
+            units = units.checked_add(if negative { -1 } else { 1 })?;
+        }
+    }
+    Some(units)
+}
+
+// Column lookup by any of the given header names.
+struct Header(StringRecord);
+
+impl Header {
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.0.iter().position(|h| names.contains(&h.trim()))
+    }
+
+    fn require(&self, names: &[&str], app: &str) -> Result<usize, Vec<String>> {
+        self.find(names)
+            .ok_or_else(|| vec![format!("Not a {} export: missing column {}", app, names[0])])
+    }
+}
+
+fn field(record: &StringRecord, col: Option<usize>) -> &str {
+    col.and_then(|c| record.get(c)).map(str::trim).unwrap_or("")
+}
+
+fn line_of(record: &StringRecord) -> u64 {
+    record.position().map_or(0, |p| p.line())
+}
+
+fn read_records(text: &str) -> Result<(Header, Vec<StringRecord>), Vec<String>> {
+    let mut rdr = ReaderBuilder::new().flexible(true).from_reader(text.as_bytes());
+    let header = Header(rdr.headers().map_err(|e| vec![e.to_string()])?.clone());
+    let records = rdr.records().collect::<Result<Vec<_>, _>>().map_err(|e| vec![e.to_string()])?;
+    Ok((header, records))
+}
@@ -0,0 +1,214 @@
+// ISO 20022 camt.053 bank-to-customer statements (any 001.xx version; elements
+// are matched by local name, so the namespace does not matter).
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use ledger_module::{ImportItem, Kind, NewEntry};
+use roxmltree::{Document, Node};
+
+use super::{AppImport, BankOptions, join_note, whole_units};
+
+/// Parses the booked entries (`Sts` = BOOK) of every `Stmt`. Pending entries
+/// are counted as excluded. An entry that batches several transactions with
+/// their own amounts becomes one entry per transaction. References are the
+/// bank's (`AcctSvcrRef`, then `NtryRef`, then `EndToEndId`), prefixed with
+/// the account.
+pub fn read_camt053(text: &str, opts: &BankOptions) -> Result<AppImport, Vec<String>> {
+    let doc = Document::parse(text).map_err(|e| vec![format!("Invalid XML: {}", e)])?;
+    let mut out = AppImport { items: Vec::new(), transfers: 0, excluded: 0 };
+    let mut errors = Vec::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+    for stmt in doc.descendants().filter(|n| is(n, "Stmt")) {
+        let account = path_text(stmt, &["Acct", "Id", "IBAN"])
+            .or_else(|| path_text(stmt, &["Acct", "Id", "Othr", "Id"]))
+            .unwrap_or("");
+        for ntry in children(stmt, "Ntry") {
+            let line = doc.text_pos_at(ntry.range().start).row;
+            let status = path_text(ntry, &["Sts", "Cd"]).or_else(|| path_text(ntry, &["Sts"])).unwrap_or("");
+            if status != "BOOK" {
+                out.excluded += 1;
+                continue;
+            }
+            let Some(date) = date_of(ntry, "ValDt").or_else(|| date_of(ntry, "BookgDt")) else {
+                errors.push(format!("line {}: entry without a value or booking date", line));
+                continue;
+            };
+            let txs: Vec<Node> = children(ntry, "NtryDtls").flat_map(|d| children(d, "TxDtls")).collect();
+            let batched = txs.len() > 1 && txs.iter().all(|t| tx_amount(*t).is_some());
+            let parts: Vec<(Option<Node>, &[Node])> = if batched {
+                txs.iter().map(|t| (Some(*t), std::slice::from_ref(t))).collect()
+            } else {
+                vec![(None, &txs[..])]
+            };
+            for (tx, details) in parts {
+                let amount = tx.and_then(tx_amount).or_else(|| path_text(ntry, &["Amt"]));
+                let indicator = tx
+                    .and_then(|t| path_text(t, &["CdtDbtInd"]))
+                    .or_else(|| path_text(ntry, &["CdtDbtInd"]));
+                let kind = match indicator {
+                    Some("DBIT") => Kind::Expense,
+                    Some("CRDT") => Kind::Income,
+                    other => {
+                        errors.push(format!("line {}: invalid CdtDbtInd {:?}", line, other.unwrap_or("")));
+                        continue;
+                    }
+                };
+                let Some(units) = amount.and_then(|a| whole_units(a, '.', opts.round)?.checked_abs()) else {
+                    errors.push(format!(
+                        "line {}: invalid amount '{}' (amounts must be whole; see --round)",
+                        line,
+                        amount.unwrap_or("")
+                    ));
+                    continue;
+                };
+                if units == 0 {
+                    out.excluded += 1;
+                    continue;
+                }
+
+                let mut entry = NewEntry::new(kind, units, &opts.category);
+                entry.created_at = date.and_hms_opt(0, 0, 0);
+                entry.account = opts.account.clone().or_else(|| (!account.is_empty()).then(|| account.to_string()));
+                let party = if kind == Kind::Expense { "Cdtr" } else { "Dbtr" };
+                let names: Vec<&str> = details.iter().filter_map(|t| party_name(*t, party)).collect();
+                let remittance: Vec<&str> = details
+                    .iter()
+                    .flat_map(|t| t.descendants().filter(|n| is(n, "Ustrd") || is(n, "AddtlTxInf")))
+                    .filter_map(|n| n.text())
+                    .map(str::trim)
+                    .collect();
+                entry.note = join_note(&[&names.join(", "), &remittance.join(" ")])
+                    .or_else(|| path_text(ntry, &["AddtlNtryInf"]).map(str::to_string));
+
+                let usable = |r: &&str| !r.is_empty() && *r != "NOTPROVIDED";
+                let tx_ref = |t: Node<'_, '_>| {
+                    path_text(t, &["Refs", "AcctSvcrRef"])
+                        .filter(usable)
+                        .or_else(|| path_text(t, &["Refs", "EndToEndId"]).filter(usable))
+                        .map(str::to_string)
+                };
+                let entry_ref = path_text(ntry, &["AcctSvcrRef"])
+                    .or_else(|| path_text(ntry, &["NtryRef"]))
+                    .filter(usable)
+                    .map(str::to_string);
+                let bank_ref = match tx {
+                    // One transaction of a batch: its own reference, or the
+                    // entry's with the transaction's position.
+                    Some(t) => tx_ref(t).or_else(|| {
+                        let i = txs.iter().position(|x| *x == t).unwrap_or(0);
+                        entry_ref.as_ref().map(|r| format!("{}/{}", r, i + 1))
+                    }),
+                    None => entry_ref.or_else(|| details.first().and_then(|t| tx_ref(*t))),
+                };
+                let reference = match bank_ref {
+                    Some(r) => format!("{}|{}", account, r),
+                    None => {
+                        let fingerprint = format!(
+                            "{}|{}|{}|{}|{}",
+                            account,
+                            date,
+                            indicator.unwrap_or(""),
+                            amount.unwrap_or(""),
+                            entry.note.as_deref().unwrap_or("")
+                        );
+                        let n = occurrences.entry(fingerprint.clone()).or_default();
+                        *n += 1;
+                        format!("{}#{}", fingerprint, n)
+                    }
+                };
+                out.items.push(ImportItem { entry, reference });
+            }
+        }
+    }
+    if errors.is_empty() { Ok(out) } else { Err(errors) }
+}
+
+fn is(n: &Node, name: &str) -> bool {
+    n.is_element() && n.tag_name().name() == name
+}
+
+fn children<'a, 'i>(n: Node<'a, 'i>, name: &'static str) -> impl Iterator<Item = Node<'a, 'i>> {
+    n.children().filter(move |c| is(c, name))
+}
+
+fn path_text<'a>(n: Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    let mut node = n;
+    for name in path {
+        node = node.children().find(|c| is(c, name))?;
+    }
+    node.text().map(str::trim)
+}
+
+// <ValDt><Dt>2026-09-01</Dt></ValDt> or <ValDt><DtTm>2026-09-01T10:00:00</DtTm></ValDt>
+fn date_of(n: Node, element: &str) -> Option<NaiveDate> {
+    let text = path_text(n, &[element, "Dt"]).or_else(|| path_text(n, &[element, "DtTm"]))?;
+    NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok()
+}
+
+// A transaction's own amount: <Amt> (newer versions) or <AmtDtls><TxAmt><Amt>.
+fn tx_amount<'a>(tx: Node<'a, '_>) -> Option<&'a str> {
+    path_text(tx, &["Amt"]).or_else(|| path_text(tx, &["AmtDtls", "TxAmt", "Amt"]))
+}
+
+// <RltdPties><Cdtr><Nm> or, from version 08, <RltdPties><Cdtr><Pty><Nm>.
+fn party_name<'a>(tx: Node<'a, '_>, party: &str) -> Option<&'a str> {
+    let node = tx.children().find(|c| is(c, "RltdPties"))?.children().find(|c| is(c, party))?;
+    node.descendants().find(|c| is(c, "Nm")).and_then(|n| n.text()).map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(round: bool) -> BankOptions {
+        BankOptions { category: "未分類".to_string(), account: None, round }
+    }
+
+    #[test]
+    fn statement_file_is_imported() {
+        let text = include_str!("../../tests/fixtures/statement.camt053.xml");
+        let r = read_camt053(text, &opts(true)).unwrap();
+        let summary: Vec<(Kind, i64, &str, Option<&str>)> = r
+            .items
+            .iter()
+            .map(|i| (i.entry.kind, i.entry.amount, i.reference.as_str(), i.entry.note.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    Kind::Expense,
+                    1200,
+                    "DE89370400440532013000|REF-001",
+                    Some("Landlord GmbH / Office rent September")
+                ),
+                (Kind::Income, 100, "DE89370400440532013000|INV-1", Some("Client A / Invoice 1")),
+                (Kind::Income, 200, "DE89370400440532013000|INV-2", Some("Client B / Invoice 2")),
+                (Kind::Expense, 10, "DE89370400440532013000|REF-003", Some("Card fee")),
+            ]
+        );
+        // The pending entry.
+        assert_eq!(r.excluded, 1);
+        assert_eq!(r.items[0].entry.created_at, NaiveDate::from_ymd_opt(2026, 9, 1).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(r.items[0].entry.account.as_deref(), Some("DE89370400440532013000"));
+
+        let errors = read_camt053(text, &opts(false)).err().unwrap();
+        assert_eq!(errors, vec!["line 26: invalid amount '9.99' (amounts must be whole; see --round)".to_string()]);
+    }
+
+    #[test]
+    fn amounts_out_of_range_are_rejected() {
+        for amount in ["-9223372036854775808", "9223372036854775807.5", "9223372036854775808"] {
+            let text = format!(
+                "<Document><BkToCstmrStmt><Stmt><Ntry><Amt>{}</Amt><CdtDbtInd>DBIT</CdtDbtInd>\
+                 <Sts>BOOK</Sts><ValDt><Dt>2026-09-01</Dt></ValDt></Ntry></Stmt></BkToCstmrStmt></Document>",
+                amount
+            );
+            let errors = read_camt053(&text, &opts(true)).err().unwrap();
+            assert_eq!(errors.len(), 1, "{}", amount);
+            assert!(errors[0].contains(&format!("invalid amount '{}'", amount)), "{:?}", errors);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// MoneyForward ME CSV export (収入・支出詳細): 計算対象, 日付, 内容, 金額（円）,
+// 保有金融機関, 大項目, 中項目, メモ, 振替, ID
+
+use ledger_module::{ImportItem, Kind, NewEntry};
+
+use super::{AppImport, Subcategory, categorize, field, join_note, line_of, parse_amount, parse_datetime, read_records};
+
+const APP: &str = "MoneyForward ME";
+
+/// Parses a MoneyForward ME export. The sign of the amount gives the kind;
+/// rows flagged as transfers (振替 = 1) or left out of the totals
+/// (計算対象 = 0) are skipped. Rows are identified by MoneyForward's ID.
+pub fn read_moneyforward(text: &str, mode: Subcategory) -> Result<AppImport, Vec<String>> {
+    let (h, records) = read_records(text)?;
+    let date_col = h.require(&["日付"], APP)?;
+    let amount_col = h.require(&["金額（円）", "金額(円)", "金額"], APP)?;
+    let id_col = h.require(&["ID"], APP)?;
+    let counted_col = h.find(&["計算対象"]);
+    let content_col = h.find(&["内容"]);
+    let account_col = h.find(&["保有金融機関"]);
+    let major_col = h.find(&["大項目"]);
+    let minor_col = h.find(&["中項目"]);
+    let memo_col = h.find(&["メモ"]);
+    let transfer_col = h.find(&["振替"]);
+
+    let mut out = AppImport { items: Vec::new(), transfers: 0, excluded: 0 };
+    let mut errors = Vec::new();
+    for r in &records {
+        let line = line_of(r);
+        let f = |col| field(r, col);
+        if f(transfer_col) == "1" {
+            out.transfers += 1;
+            continue;
+        }
+        if f(counted_col) == "0" {
+            out.excluded += 1;
+            continue;
+        }
+        // `checked_abs` rejects the one amount whose sign cannot be flipped.
+        let Some((signed, amount)) = parse_amount(f(Some(amount_col))).and_then(|a| Some((a, a.checked_abs()?))) else {
+            errors.push(format!("line {}: invalid amount '{}'", line, f(Some(amount_col))));
+            continue;
+        };
+        if signed == 0 {
+            out.excluded += 1;
+            continue;
+        }
+        let Some(created_at) = parse_datetime(f(Some(date_col))) else {
+            errors.push(format!("line {}: invalid date '{}'", line, f(Some(date_col))));
+            continue;
+        };
+        let id = f(Some(id_col));
+        if id.is_empty() {
+            errors.push(format!("line {}: missing ID", line));
+            continue;
+        }
+
+        let kind = if signed < 0 { Kind::Expense } else { Kind::Income };
+        let mut entry = NewEntry::new(kind, amount, "");
+        categorize(&mut entry, f(major_col), f(minor_col), mode);
+        entry.created_at = Some(created_at);
+        entry.note = join_note(&[f(content_col), f(memo_col)]);
+        let account = f(account_col);
+        entry.account = (!account.is_empty()).then(|| account.to_string());
+        out.items.push(ImportItem { entry, reference: id.to_string() });
+    }
+    if errors.is_empty() { Ok(out) } else { Err(errors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::TextEncoding;
+
+    const HEADER: &str = "計算対象,日付,内容,金額（円）,保有金融機関,大項目,中項目,メモ,振替,ID\n";
+
+    fn fixture() -> String {
+        // Saved as MoneyForward writes it: Shift_JIS.
+        TextEncoding::ShiftJis.decode(include_bytes!("../../tests/fixtures/moneyforward.csv")).unwrap()
+    }
+
+    #[test]
+    fn export_file_is_imported() {
+        let r = read_moneyforward(&fixture(), Subcategory::Tag).unwrap();
+        let summary: Vec<(Kind, i64, &str, &str)> = r
+            .items
+            .iter()
+            .map(|i| (i.entry.kind, i.entry.amount, i.entry.category.as_str(), i.reference.as_str()))
+            .collect();
+        assert_eq!(summary, [(Kind::Expense, 980, "食費", "abc123"), (Kind::Income, 300000, "収入", "abc124")]);
+        assert_eq!(r.items[0].entry.tags, ["外食"]);
+        assert_eq!(r.items[0].entry.account.as_deref(), Some("楽天カード"));
+        assert_eq!(r.items[0].entry.note.as_deref(), Some("ランチ"));
+        assert_eq!(r.items[1].entry.note.as_deref(), Some("給与 株式会社X"));
+        // 振替 = 1 and 計算対象 = 0.
+        assert_eq!((r.transfers, r.excluded), (1, 1));
+
+        let joined = read_moneyforward(&fixture(), Subcategory::Join).unwrap();
+        let categories: Vec<&str> = joined.items.iter().map(|i| i.entry.category.as_str()).collect();
+        assert_eq!(categories, ["食費/外食", "収入/給与"]);
+        assert!(joined.items.iter().all(|i| i.entry.tags.is_empty()));
+    }
+
+    #[test]
+    fn repeated_rows_keep_their_id() {
+        let row = "1,2026/09/05,ランチ,-980,楽天カード,食費,外食,,0,abc123\n";
+        let r = read_moneyforward(&format!("{}{}{}", HEADER, row, row), Subcategory::Tag).unwrap();
+        let references: Vec<&str> = r.items.iter().map(|i| i.reference.as_str()).collect();
+        assert_eq!(references, ["abc123", "abc123"]);
+    }
+
+    #[test]
+    fn bad_rows_are_reported_by_line() {
+        let text = format!(
+            "{}{}{}{}{}",
+            HEADER,
+            "1,2026/09/05,ランチ,-980,楽天カード,食費,外食,,0,abc123\n",
+            "1,2026/09/06,ランチ,千円,楽天カード,食費,外食,,0,abc124\n",
+            "1,2026/09/07,ランチ,-9223372036854775808,楽天カード,食費,外食,,0,abc125\n",
+            "1,9月8日,ランチ,-980,楽天カード,食費,外食,,0,abc126\n1,2026/09/09,ランチ,-980,楽天カード,食費,外食,,0,\n",
+        );
+        let errors = read_moneyforward(&text, Subcategory::Tag).err().unwrap();
+        assert_eq!(
+            errors,
+            [
+                "line 3: invalid amount '千円'",
+                "line 4: invalid amount '-9223372036854775808'",
+                "line 5: invalid date '9月8日'",
+                "line 6: missing ID",
+            ]
+        );
+        let errors = read_moneyforward("日付,金額\n2026/09/05,-980\n", Subcategory::Tag).err().unwrap();
+        assert_eq!(errors, ["Not a MoneyForward ME export: missing column ID"]);
+    }
+}
@@ -0,0 +1,246 @@
+// SWIFT MT940 customer statements: :25: account, :61: statement lines and the
+// :86: information that follows each of them.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use ledger_module::{ImportItem, Kind, NewEntry};
+
+use super::{AppImport, BankOptions, join_note, whole_units};
+
+struct StatementLine {
+    line: usize,
+    value_date: NaiveDate,
+    debit: bool,
+    amount: String,
+    customer_ref: String,
+    bank_ref: String,
+    info: String,
+}
+
+/// Parses every :61: line. All MT940 lines are booked (pending items come in
+/// MT942). References are the bank's (after `//`), then the customer's,
+/// prefixed with the :25: account.
+pub fn read_mt940(text: &str, opts: &BankOptions) -> Result<AppImport, Vec<String>> {
+    let mut out = AppImport { items: Vec::new(), transfers: 0, excluded: 0 };
+    let mut errors = Vec::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut account = String::new();
+    let mut lines: Vec<(String, StatementLine)> = Vec::new();
+
+    for (line, tag, value) in fields(text) {
+        match tag.as_str() {
+            "25" => account = value.trim().to_string(),
+            "61" => match parse_61(&value, line) {
+                Some(l) => lines.push((account.clone(), l)),
+                None => errors.push(format!("line {}: invalid :61: field '{}'", line, value.lines().next().unwrap_or(""))),
+            },
+            "86" => {
+                if let Some((_, l)) = lines.last_mut().filter(|(_, l)| l.info.is_empty()) {
+                    l.info = parse_86(&value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (account, l) in lines {
+        let Some(units) = whole_units(&l.amount, ',', opts.round) else {
+            errors.push(format!(
+                "line {}: invalid amount '{}' (amounts must be whole; see --round)",
+                l.line, l.amount
+            ));
+            continue;
+        };
+        if units == 0 {
+            out.excluded += 1;
+            continue;
+        }
+        let kind = if l.debit { Kind::Expense } else { Kind::Income };
+        let mut entry = NewEntry::new(kind, units, &opts.category);
+        entry.created_at = l.value_date.and_hms_opt(0, 0, 0);
+        entry.account = opts.account.clone().or_else(|| (!account.is_empty()).then(|| account.clone()));
+        entry.note = join_note(&[&l.info]);
+
+        let usable = |r: &str| !r.is_empty() && r != "NONREF";
+        let reference = if usable(&l.bank_ref) {
+            format!("{}|{}", account, l.bank_ref)
+        } else if usable(&l.customer_ref) {
+            format!("{}|{}", account, l.customer_ref)
+        } else {
+            let fingerprint = format!("{}|{}|{}|{}|{}", account, l.value_date, l.debit, l.amount, l.info);
+            let n = occurrences.entry(fingerprint.clone()).or_default();
+            *n += 1;
+            format!("{}#{}", fingerprint, n)
+        };
+        out.items.push(ImportItem { entry, reference });
+    }
+    if errors.is_empty() { Ok(out) } else { Err(errors) }
+}
+
+// Splits the text into (line, tag, value) fields. A field runs until the
+// next line starting with `:tag:`; SWIFT envelope lines ({1:...}, -}) are
+// dropped.
+fn fields(text: &str) -> Vec<(usize, String, String)> {
+    let mut out: Vec<(usize, String, String)> = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.trim_end();
+        if line.starts_with('{') || line == "-}" || line == "-" {
+            continue;
+        }
+        let tag = line
+            .strip_prefix(':')
+            .and_then(|rest| rest.split_once(':'))
+            .filter(|(tag, _)| (2..=3).contains(&tag.len()) && tag.starts_with(|c: char| c.is_ascii_digit()));
+        match tag {
+            Some((tag, value)) => out.push((i + 1, tag.to_string(), value.to_string())),
+            None => {
+                if let Some((_, _, value)) = out.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+    out
+}
+
+// YYMMDD[MMDD]<C|D|RC|RD>[funds code]<amount><type:4>[customer ref][//bank ref]
+fn parse_61(value: &str, line: usize) -> Option<StatementLine> {
+    let first = value.lines().next()?;
+    let value_date = NaiveDate::parse_from_str(first.get(..6)?, "%y%m%d").ok()?;
+    let mut rest = &first[6..];
+    if rest.get(..4).is_some_and(|entry_date| entry_date.bytes().all(|b| b.is_ascii_digit())) {
+        rest = &rest[4..];
+    }
+    // A reversal of a credit takes money out, a reversal of a debit puts it back.
+    let (debit, rest) = if let Some(r) = rest.strip_prefix("RC") {
+        (true, r)
+    } else if let Some(r) = rest.strip_prefix("RD") {
+        (false, r)
+    } else if let Some(r) = rest.strip_prefix('C') {
+        (false, r)
+    } else {
+        (true, rest.strip_prefix('D')?)
+    };
+    let rest = rest.strip_prefix(|c: char| c.is_ascii_alphabetic()).unwrap_or(rest);
+    let amount_len = rest.find(|c: char| !(c.is_ascii_digit() || c == ',')).unwrap_or(rest.len());
+    if amount_len == 0 {
+        return None;
+    }
+    let amount = rest[..amount_len].to_string();
+    let refs = rest[amount_len..].get(4..).unwrap_or("");
+    let (customer_ref, bank_ref) = refs.split_once("//").unwrap_or((refs, ""));
+    Some(StatementLine {
+        line,
+        value_date,
+        debit,
+        amount,
+        customer_ref: customer_ref.trim().to_string(),
+        bank_ref: bank_ref.trim().to_string(),
+        info: String::new(),
+    })
+}
+
+// Structured :86: (`ddd?00posting text?20remittance...?32name`) keeps the
+// remittance (?20-?29, ?60-?63) and counterparty name (?32, ?33); anything
+// else is used as is.
+fn parse_86(value: &str) -> String {
+    let flat: String = value.lines().collect();
+    let subfields = flat
+        .get(..3)
+        .filter(|code| code.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|_| flat[3..].strip_prefix('?'));
+    let Some(subfields) = subfields else {
+        return value.lines().map(str::trim).collect::<Vec<_>>().join(" ").trim().to_string();
+    };
+    let mut remittance = String::new();
+    let mut name = String::new();
+    for sub in subfields.split('?') {
+        let (Some(code), Some(text)) = (sub.get(..2), sub.get(2..)) else {
+            continue;
+        };
+        match code {
+            "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" | "60" | "61" | "62" | "63" => {
+                remittance.push_str(text)
+            }
+            "32" | "33" => name.push_str(text),
+            _ => {}
+        }
+    }
+    join_note(&[name.trim(), remittance.trim()]).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts() -> BankOptions {
+        BankOptions { category: "未分類".to_string(), account: None, round: true }
+    }
+
+    #[test]
+    fn statement_lines_are_parsed() {
+        let l = parse_61("2609010901D1200,00NTRFNONREF//BANKREF001", 3).unwrap();
+        assert_eq!(l.value_date, NaiveDate::from_ymd_opt(2026, 9, 1).unwrap());
+        assert!(l.debit);
+        assert_eq!(l.amount, "1200,00");
+        assert_eq!((l.customer_ref.as_str(), l.bank_ref.as_str()), ("NONREF", "BANKREF001"));
+
+        // No entry date, a reversal and a funds code.
+        let l = parse_61("260905RDR300,00NTRFINV-1", 4).unwrap();
+        assert!(!l.debit);
+        assert_eq!(l.amount, "300,00");
+        assert_eq!((l.customer_ref.as_str(), l.bank_ref.as_str()), ("INV-1", ""));
+    }
+
+    #[test]
+    fn broken_statement_lines_are_rejected() {
+        for value in ["250901é€D12,00NTRF", "25090é", "2509011é€", "250901", "2509010901D", "2509010901X12,00NTRF"] {
+            assert!(parse_61(value, 1).is_none(), "{}", value);
+        }
+        let text = ":25:123\n:61:250901é€D12,00NTRF\n:86:Coffee\n:61:250902D5,00NTRFNONREF\n";
+        let errors = read_mt940(text, &opts()).err().unwrap();
+        assert_eq!(errors, vec!["line 2: invalid :61: field '250901é€D12,00NTRF'".to_string()]);
+    }
+
+    #[test]
+    fn information_is_parsed() {
+        assert_eq!(parse_86("ÄÖÜ payment"), "ÄÖÜ payment");
+        assert_eq!(parse_86("1é?20x"), "1é?20x");
+        assert_eq!(parse_86("12"), "12");
+        assert_eq!(parse_86("166?00GUTSCHRIFT?20Rechnung 4711?32Müller GmbH"), "Müller GmbH / Rechnung 4711");
+        // Structured fields wrap mid-word; free text wraps between words.
+        assert_eq!(
+            parse_86("177?00SEPA-UEBERWEISUNG?20Office rent Sep\n?21tember?32Landlord GmbH"),
+            "Landlord GmbH / Office rent September"
+        );
+        assert_eq!(parse_86("Payment from Client A\n second line"), "Payment from Client A second line");
+    }
+
+    #[test]
+    fn statement_file_is_imported() {
+        let r = read_mt940(include_str!("../../tests/fixtures/statement.sta"), &opts()).unwrap();
+        let summary: Vec<(Kind, i64, &str, Option<&str>)> = r
+            .items
+            .iter()
+            .map(|i| (i.entry.kind, i.entry.amount, i.reference.as_str(), i.entry.note.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    Kind::Expense,
+                    1200,
+                    "37040044/0532013000|BANKREF001",
+                    Some("Landlord GmbH / Office rent September")
+                ),
+                (Kind::Income, 300, "37040044/0532013000|INV-1", Some("Payment from Client A second line")),
+                (Kind::Expense, 10, "37040044/0532013000|2026-09-07|true|9,99|#1", None),
+            ]
+        );
+        assert_eq!(r.items[0].entry.account.as_deref(), Some("37040044/0532013000"));
+        assert!(read_mt940(include_str!("../../tests/fixtures/statement.sta"), &BankOptions { round: false, ..opts() })
+            .is_err());
+    }
+}
@@ -0,0 +1,152 @@
+// Zaim CSV export: 日付, 方法, カテゴリ, カテゴリの内訳, 支払元, 入金先, 品目,
+// メモ, お店, 通貨, 収入, 支出, 振替, 残高調整, ...
+
+use std::collections::HashMap;
+
+use ledger_module::{ImportItem, Kind, NewEntry};
+
+use super::{AppImport, Subcategory, categorize, field, join_note, line_of, parse_amount, parse_datetime, read_records};
+
+const APP: &str = "Zaim";
+
+/// Parses a Zaim export. Zaim has no transaction id, so each row is
+/// identified by its contents (and its position among identical rows), which
+/// stays the same across overlapping exports.
+pub fn read_zaim(text: &str, mode: Subcategory) -> Result<AppImport, Vec<String>> {
+    let (h, records) = read_records(text)?;
+    let date_col = h.require(&["日付"], APP)?;
+    let income_col = h.require(&["収入"], APP)?;
+    let payment_col = h.require(&["支出"], APP)?;
+    let method_col = h.find(&["方法"]);
+    let category_col = h.find(&["カテゴリ"]);
+    let genre_col = h.find(&["カテゴリの内訳"]);
+    let from_col = h.find(&["支払元"]);
+    let to_col = h.find(&["入金先"]);
+    let item_col = h.find(&["品目"]);
+    let memo_col = h.find(&["メモ"]);
+    let place_col = h.find(&["お店"]);
+
+    let mut out = AppImport { items: Vec::new(), transfers: 0, excluded: 0 };
+    let mut errors = Vec::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    for r in &records {
+        let line = line_of(r);
+        let f = |col| field(r, col);
+        let (income, payment) = match (parse_amount(f(Some(income_col))), parse_amount(f(Some(payment_col)))) {
+            (Some(i), Some(p)) => (i, p),
+            _ => {
+                errors.push(format!("line {}: invalid amount", line));
+                continue;
+            }
+        };
+        let kind = match f(method_col) {
+            "payment" | "支出" => Kind::Expense,
+            "income" | "収入" => Kind::Income,
+            "transfer" | "振替" => {
+                out.transfers += 1;
+                continue;
+            }
+            // Balance adjustments and rows without a method.
+            _ if income > 0 && payment == 0 => Kind::Income,
+            _ if payment > 0 && income == 0 => Kind::Expense,
+            _ => {
+                out.excluded += 1;
+                continue;
+            }
+        };
+        let amount = if kind == Kind::Expense { payment } else { income };
+        if amount <= 0 {
+            out.excluded += 1;
+            continue;
+        }
+        let Some(created_at) = parse_datetime(f(Some(date_col))) else {
+            errors.push(format!("line {}: invalid date '{}'", line, f(Some(date_col))));
+            continue;
+        };
+
+        let mut entry = NewEntry::new(kind, amount, "");
+        categorize(&mut entry, f(category_col), f(genre_col), mode);
+        entry.created_at = Some(created_at);
+        entry.note = join_note(&[f(item_col), f(place_col), f(memo_col)]);
+        let account = if kind == Kind::Expense { f(from_col) } else { f(to_col) };
+        entry.account = (!account.is_empty() && account != "-").then(|| account.to_string());
+
+        let fingerprint = [date_col, income_col, payment_col]
+            .into_iter()
+            .map(Some)
+            .chain([method_col, category_col, genre_col, from_col, to_col, item_col, memo_col, place_col])
+            .map(f)
+            .collect::<Vec<_>>()
+            .join("|");
+        let n = occurrences.entry(fingerprint.clone()).or_default();
+        *n += 1;
+        out.items.push(ImportItem { entry, reference: format!("{}#{}", fingerprint, n) });
+    }
+    if errors.is_empty() { Ok(out) } else { Err(errors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "日付,方法,カテゴリ,カテゴリの内訳,支払元,入金先,品目,メモ,お店,通貨,収入,支出,振替\n";
+
+    #[test]
+    fn export_file_is_imported() {
+        let text = include_str!("../../tests/fixtures/zaim.csv");
+        let r = read_zaim(text, Subcategory::Tag).unwrap();
+        let summary: Vec<(Kind, i64, &str, Option<&str>)> = r
+            .items
+            .iter()
+            .map(|i| (i.entry.kind, i.entry.amount, i.entry.category.as_str(), i.entry.note.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Kind::Expense, 250, "食費", Some("牛乳 / スーパーA")),
+                (Kind::Expense, 250, "食費", Some("牛乳 / スーパーA")),
+                (Kind::Income, 250000, "給与", Some("9月分")),
+                (Kind::Expense, 1280, "日用雑貨", Some("洗剤 / ドラッグストア / 詰め替え")),
+                (Kind::Expense, 980, "食費", Some("ランチ")),
+                (Kind::Expense, 250, "食費", Some("牛乳 / スーパーA")),
+            ]
+        );
+        // Expenses come from 支払元, income goes to 入金先.
+        let accounts: Vec<Option<&str>> = r.items.iter().map(|i| i.entry.account.as_deref()).collect();
+        assert_eq!(accounts, [Some("財布"), Some("財布"), Some("銀行"), Some("カード"), Some("カード"), Some("財布")]);
+        let tags: Vec<Vec<String>> = r.items.iter().map(|i| i.entry.tags.clone()).collect();
+        assert_eq!(tags, [vec!["食料品"], vec!["食料品"], vec![], vec![], vec!["外食"], vec!["食料品"]]);
+        // `transfer` and `振替` rows.
+        assert_eq!((r.transfers, r.excluded), (2, 0));
+
+        let joined = read_zaim(text, Subcategory::Join).unwrap();
+        let categories: Vec<&str> = joined.items.iter().map(|i| i.entry.category.as_str()).collect();
+        assert_eq!(categories, ["食費/食料品", "食費/食料品", "給与", "日用雑貨", "食費/外食", "食費/食料品"]);
+    }
+
+    #[test]
+    fn identical_rows_are_numbered() {
+        let r = read_zaim(include_str!("../../tests/fixtures/zaim.csv"), Subcategory::Tag).unwrap();
+        let milk = "2026-09-01|0|250|payment|食費|食料品|財布|-|牛乳||スーパーA";
+        let references: Vec<&str> = r.items.iter().map(|i| i.reference.as_str()).collect();
+        assert_eq!(references[0], format!("{}#1", milk));
+        assert_eq!(references[1], format!("{}#2", milk));
+        assert_eq!(references[5], format!("{}#3", milk));
+        assert!(references[2..5].iter().all(|r| r.ends_with("#1")));
+    }
+
+    #[test]
+    fn bad_rows_are_reported_by_line() {
+        let text = format!(
+            "{}{}{}{}",
+            HEADER,
+            "2026-09-01,payment,食費,食料品,財布,-,牛乳,,,JPY,0,250,0\n",
+            "2026-09-02,payment,食費,食料品,財布,-,牛乳,,,JPY,0,二百,0\n",
+            "9月3日,payment,食費,食料品,財布,-,牛乳,,,JPY,0,250,0\n",
+        );
+        let errors = read_zaim(&text, Subcategory::Tag).err().unwrap();
+        assert_eq!(errors, ["line 3: invalid amount", "line 4: invalid date '9月3日'"]);
+        let errors = read_zaim("日付,収入\n2026-09-01,0\n", Subcategory::Tag).err().unwrap();
+        assert_eq!(errors, ["Not a Zaim export: missing column 支出"]);
+    }
+}
@@ -1,65 +1,766 @@
+mod chart;
+mod encoding;
+mod html;
+mod import;
+mod pdf;
+mod qif;
+mod xlsx;
+
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::Path;
+use chrono::NaiveDate;
 use csv::WriterBuilder;
+use encoding::{ENCODING_NAMES, TextEncoding, encode_record, take_encoding_args};
 use ledger_module::{
-    add_entry, category_totals_by_kind, category_totals_by_kind_in_range,
-    delete_entry, init_db, list_entries, month_summary, open_db,
-    summary_in_range, Kind, entries_in_month, entries_in_range, Entry
+    category_totals_by_kind, category_totals_by_kind_in_range,
+    delete_entry, entry_history, init_db, list_trash, month_summary,
+    open_db, purge_trash, recent_changes, redo, restore_entry, summary_in_range,
+    undo, update_entry, AuditRecord, InvalidYearMonth, YearMonth, Granularity, PeriodTotal,
+    period_summaries, load_period_settings, category_month_pivot, PivotTable,
+    compare_periods, Delta, PeriodComparison, save_period_settings, PeriodSettings, Kind, EntryFilter, NewEntry, SortOrder,
+    for_each_entry, insert_entry, run_operation, Connection, already_imported, query_entries, auto_backup, backup_database, list_backups,
+    load_backup_retention, restore_database, save_backup_retention, verify_database, import_entries, add_recurring, list_recurring, remove_recurring,
+    NewRecurring, RecurringRule, forecast, Forecast, FORECAST_HISTORY_MONTHS, FORECAST_MONTHS, find_anomalies, Anomalies, Entry,
+    detect_subscriptions, Interval, stats, Stats, CategoryTotal, entries_in_month, entries_in_range,
+    backup_dir, change_passphrase, is_encrypted, unlock, DB_PATH, ENCRYPTION_SUPPORTED, diagnose, repair, vacuum
 };
 
-fn current_ym() -> String {
-    let now = chrono::Local::now();
-    now.format("%Y-%m").to_string()
+fn current_ym(period: &PeriodSettings) -> YearMonth {
+    period.month_of(chrono::Local::now().date_naive())
+}
+
+// Optional `YYYY-MM` positional argument; the current month when absent.
+fn month_arg(arg: Option<&String>, period: &PeriodSettings) -> Result<YearMonth, InvalidYearMonth> {
+    match arg {
+        Some(a) if !a.starts_with("--") => a.parse(),
+        _ => Ok(current_ym(period)),
+    }
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| format!("Invalid date: {} (expected YYYY-MM-DD)", s))
+}
+
+fn parse_kind(s: &str) -> Option<Kind> {
+    match s {
+        "expense" => Some(Kind::Expense),
+        "income" => Some(Kind::Income),
+        _ => None,
+    }
+}
+
+fn print_audit_record(r: &AuditRecord) {
+    println!("{} #{} {} entry {} by {}", r.changed_at, r.id, r.action, r.entry_id, r.actor);
+    if let Some(before) = &r.before {
+        println!("    before: {}", before);
+    }
+    if let Some(after) = &r.after {
+        println!("    after : {}", after);
+    }
+}
+
+const FILTER_USAGE: &str = "[--month YYYY-MM] [--from YYYY-MM-DD] [--to YYYY-MM-DD] [--kind expense|income] \
+[--category C]... [--min N] [--max N] [--note TEXT] [--account A] [--tag T]... \
+[--sort date|-date|amount|-amount] [--limit N] [--offset N]";
+
+// Builds an EntryFilter from `--flag value` pairs; used by `list` and `export`.
+fn parse_filter(args: &[String], mut f: EntryFilter) -> Result<EntryFilter, String> {
+    let mut it = args.iter();
+    while let Some(flag) = it.next() {
+        let Some(value) = it.next() else {
+            return Err(format!("Missing value for {}", flag));
+        };
+        let number = || value.parse::<i64>().map_err(|_| format!("Invalid number for {}: {}", flag, value));
+        f = match flag.as_str() {
+            "--month" => f.month(value.parse().map_err(|e: InvalidYearMonth| e.to_string())?),
+            "--from" => {
+                f.from_date = Some(parse_date(value)?);
+                f
+            }
+            "--to" => {
+                f.to_date = Some(parse_date(value)?);
+                f
+            }
+            "--kind" => match parse_kind(value) {
+                Some(k) => f.kind(k),
+                None => return Err(format!("Invalid kind: {}", value)),
+            },
+            "--category" => f.category(value),
+            "--min" => f.min_amount(number()?),
+            "--max" => f.max_amount(number()?),
+            "--note" => f.note_contains(value),
+            "--account" => f.account(value),
+            "--tag" => f.tag(value),
+            "--sort" => match value.as_str() {
+                "date" => f.order(SortOrder::DateAsc),
+                "-date" => f.order(SortOrder::DateDesc),
+                "amount" => f.order(SortOrder::AmountAsc),
+                "-amount" => f.order(SortOrder::AmountDesc),
+                _ => return Err(format!("Invalid sort order: {}", value)),
+            },
+            "--limit" => f.limit(number()?),
+            "--offset" => f.offset(number()?),
+            _ => return Err(format!("Unknown option: {}", flag)),
+        };
+    }
+    Ok(f)
+}
+
+fn print_periods(title: &str, rows: &[PeriodTotal]) {
+    println!("== Summary by period {} ==", title);
+    println!("{:10} {:>12} {:>12} {:>12}", "period", "income", "expense", "balance");
+    let (mut income, mut expense) = (0, 0);
+    for r in rows {
+        println!("{:10} {:>12} {:>12} {:>12}", r.label, r.income, r.expense, r.balance);
+        income += r.income;
+        expense += r.expense;
+    }
+    println!("{:10} {:>12} {:>12} {:>12}", "total", income, expense, income - expense);
+}
+
+// `YYYY-MM` or `YYYY-MM..YYYY-MM`.
+fn parse_months_arg(s: &str) -> Option<(YearMonth, YearMonth)> {
+    if s.contains("..") {
+        parse_ym_range(s)
+    } else {
+        s.parse().ok().map(|ym| (ym, ym))
+    }
+}
+
+fn fmt_months((start, end): (YearMonth, YearMonth)) -> String {
+    if start == end { start.to_string() } else { format!("{}..{}", start, end) }
+}
+
+// 1234567 -> "1,234,567", for the HTML and PDF exports.
+fn fmt_amount(v: i64) -> String {
+    let digits = v.unsigned_abs().to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    if v < 0 { format!("-{}", out) } else { out }
+}
+
+fn fmt_delta(d: &Delta) -> String {
+    let pct = match d.percent {
+        Some(p) => format!(" ({:+.1}%)", p),
+        None => String::new(),
+    };
+    format!("{:>10} vs {:>10}  {:+}{}", d.current, d.previous, d.change, pct)
+}
+
+fn print_comparison(c: &PeriodComparison) {
+    println!("== Compare {} vs {} ==", fmt_months(c.current), fmt_months(c.previous));
+    println!("Income : {}", fmt_delta(&c.income));
+    println!("Expense: {}", fmt_delta(&c.expense));
+    println!("Balance: {}", fmt_delta(&c.balance));
+
+    let kind = |k: Kind| if k == Kind::Expense { "expense" } else { "income" };
+    for (title, rows) in [
+        ("Category changes", &c.changed),
+        ("New categories", &c.new),
+        ("Vanished categories", &c.vanished),
+    ] {
+        println!();
+        println!("== {} ==", title);
+        if rows.is_empty() {
+            println!("(none)");
+        }
+        for r in rows {
+            println!("{:12} {:8} {}", r.category, kind(r.kind), fmt_delta(&r.delta));
+        }
+    }
+}
+
+fn print_rule(r: &RecurringRule) {
+    let k = if r.kind == Kind::Expense { "Expense" } else { "Income" };
+    let every = if r.every_months == 1 { "monthly".to_string() } else { format!("every {} months", r.every_months) };
+    let until = r.end.map(|e| format!(" until {}", e)).unwrap_or_default();
+    let note = r.note.as_deref().map(|n| format!(" ({})", n)).unwrap_or_default();
+    println!(
+        "[{}] {} {} {}{} on day {}, {} from {}{}",
+        r.id,
+        k,
+        r.amount,
+        r.category,
+        note,
+        r.day,
+        every,
+        r.start,
+        until
+    );
+}
+
+fn print_forecast(f: &Forecast) {
+    println!("== Forecast from {} ==", f.as_of);
+    println!("Opening balance: {}", f.opening_balance);
+    println!("{:10} {:>12} {:>12} {:>12}", "month", "income", "expense", "balance");
+    for m in &f.months {
+        println!("{:10} {:>12} {:>12} {:>12}", m.month.to_string(), m.income, m.expense, m.balance);
+    }
+    println!();
+    match f.negative_on {
+        Some(d) => println!("Balance goes negative on {}", d),
+        None => println!("Balance stays positive"),
+    }
+
+    println!();
+    match f.history {
+        Some((start, end)) => println!("== Monthly averages {}..{} ==", start, end),
+        None => println!("== Monthly averages (no complete months yet) =="),
+    }
+    if f.averages.is_empty() {
+        println!("(none)");
+    }
+    for a in &f.averages {
+        let k = if a.kind == Kind::Expense { "expense" } else { "income" };
+        println!("{:12} {:8} {:>10}", a.category, k, a.monthly);
+    }
+    println!();
+    println!("== Recurring ==");
+    if f.rules.is_empty() {
+        println!("(none)");
+    }
+    for r in &f.rules {
+        print_rule(r);
+    }
+}
+
+fn fmt_entry(e: &Entry) -> String {
+    format!(
+        "{} {} {} {} [{}]",
+        e.created_at,
+        e.amount,
+        e.category,
+        e.note.as_deref().unwrap_or(""),
+        e.id
+    )
+}
+
+fn print_anomalies(title: &str, a: &Anomalies) {
+    println!("== Unusual amounts {} ==", title);
+    if a.outliers.is_empty() {
+        println!("(none)");
+    }
+    for o in &a.outliers {
+        println!("{}  (usual {:.0}, score {:+.1})", fmt_entry(&o.entry), o.median, o.score);
+    }
+
+    println!();
+    println!("== Category spikes {} ==", title);
+    if a.spikes.is_empty() {
+        println!("(none)");
+    }
+    for s in &a.spikes {
+        println!(
+            "{} {:12} {:>10}  (usual {:.0}, x{:.1})",
+            s.month,
+            s.category,
+            s.total,
+            s.baseline,
+            s.total as f64 / s.baseline
+        );
+    }
+
+    println!();
+    println!("== Possible duplicates {} ==", title);
+    if a.duplicates.is_empty() {
+        println!("(none)");
+    }
+    for d in &a.duplicates {
+        println!("{}", fmt_entry(&d.first));
+        println!("{}  ({} days later)", fmt_entry(&d.second), d.days_apart);
+    }
+}
+
+// Reads a passphrase without echoing it; from stdin when that is not a terminal.
+fn read_passphrase(prompt: &str) -> Result<String, String> {
+    let read = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(prompt)
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line.trim_end_matches(['\r', '\n']).to_string())
+    };
+    read.map_err(|e| format!("Failed to read passphrase: {}", e))
+}
+
+// Unlocks an encrypted ledger with LEDGER_PASSPHRASE, or by asking (three tries).
+fn unlock_db() -> Result<(), String> {
+    if !ENCRYPTION_SUPPORTED {
+        return Err(format!(
+            "{} is encrypted, but this build has no encryption support (rebuild with `--features sqlcipher`)",
+            DB_PATH
+        ));
+    }
+    if let Ok(p) = std::env::var("LEDGER_PASSPHRASE") {
+        return unlock(&p).map_err(|e| format!("Failed to unlock {}: {}", DB_PATH, e));
+    }
+    let mut tries = if std::io::stdin().is_terminal() { 3 } else { 1 };
+    loop {
+        let p = read_passphrase(&format!("Passphrase for {}: ", DB_PATH))?;
+        tries -= 1;
+        match unlock(&p) {
+            Ok(()) => return Ok(()),
+            Err(e) if tries == 0 => return Err(format!("Failed to unlock {}: {}", DB_PATH, e)),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+fn run_doctor(conn: &Connection, args: &[String], init_error: Option<ledger_module::Error>) {
+    let mut fix = false;
+    let mut compact = false;
+    for a in &args[2..] {
+        match a.as_str() {
+            "--fix" => fix = true,
+            "--vacuum" => compact = true,
+            _ => {
+                eprintln!("Usage: {} doctor [--fix] [--vacuum]", args[0]);
+                return;
+            }
+        }
+    }
+    if let Some(e) = init_error {
+        println!("database: schema migration failed: {}", e);
+    }
+    let problems = match diagnose(conn) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Failed to check the database: {}", e);
+            return;
+        }
+    };
+    for p in &problems {
+        println!("{}: {}{}", p.subject, p.message, if p.fixable() { " (fixable)" } else { "" });
+    }
+    let fixable = problems.iter().filter(|p| p.fixable()).count();
+    if problems.is_empty() {
+        println!("No problems found.");
+    } else {
+        println!("{} problem(s), {} fixable.", problems.len(), fixable);
+        if problems.iter().any(|p| p.subject == "database") {
+            println!("The file is damaged; restore a recent backup (see `backup list`).");
+        } else if fixable > 0 && !fix {
+            println!("Run `doctor --fix` to fix them.");
+        }
+    }
+
+    if (fix && fixable > 0) || compact {
+        match auto_backup(conn, "pre-doctor") {
+            Ok(Some(path)) => println!("Backed up to {}", path.display()),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Nothing changed: backup failed: {}", e);
+                return;
+            }
+        }
+    }
+    if fix && fixable > 0 {
+        match repair(conn) {
+            Ok(n) => println!("Fixed {} problem(s). `undo` reverts the changes to entries.", n),
+            Err(e) => {
+                eprintln!("Repair failed: {}", e);
+                return;
+            }
+        }
+    }
+    if compact {
+        let size = || std::fs::metadata(DB_PATH).map(|m| m.len().div_ceil(1024)).unwrap_or(0);
+        let before = size();
+        match vacuum(conn) {
+            Ok(()) => println!("Vacuumed and analyzed: {} KB -> {} KB", before, size()),
+            Err(e) => eprintln!("VACUUM failed: {}", e),
+        }
+    }
+}
+
+fn restore_from_backup(conn: &Connection, src: &Path) {
+    match verify_database(src) {
+        Ok(problems) if problems.is_empty() => {}
+        Ok(problems) => {
+            eprintln!("Not restoring from {}:", src.display());
+            for p in problems {
+                eprintln!("  {}", p);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("Failed to check {}: {}", src.display(), e);
+            return;
+        }
+    }
+    let saved = match auto_backup(conn, "pre-restore") {
+        Ok(saved) => saved,
+        Err(e) => {
+            eprintln!("Not restoring: could not back up the current data first: {}", e);
+            return;
+        }
+    };
+    let result = open_db().and_then(|mut target| restore_database(&mut target, src));
+    match (result, saved) {
+        (Ok(()), Some(saved)) => {
+            println!("Restored from {}. The previous data was saved to {}.", src.display(), saved.display())
+        }
+        (Ok(()), None) => println!("Restored from {}.", src.display()),
+        (Err(e), _) => eprintln!("Restore failed: {}", e),
+    }
 }
 
-fn parse_ym_range(s: &str) -> Option<(String, String)> {
-    let parts: Vec<&str> = s.split("..").collect();
-    if parts.len() != 2 {
-        return None;
+fn print_import_row(status: &str, e: &NewEntry) {
+    let kind = if e.kind == Kind::Expense { "Expense" } else { "Income" };
+    let date = e.created_at.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default();
+    let mut line = format!("{} {} {} {:>10} {}", status, date, kind, fmt_amount(e.amount), e.category);
+    if let Some(n) = &e.note {
+        line.push_str(&format!(" {}", n));
+    }
+    if let Some(a) = &e.account {
+        line.push_str(&format!(" @{}", a));
+    }
+    for t in &e.tags {
+        line.push_str(&format!(" #{}", t));
     }
+    println!("{}", line);
+}
+
+fn fmt_opt(v: Option<f64>, suffix: &str) -> String {
+    v.map(|v| format!("{:.1}{}", v, suffix)).unwrap_or_else(|| "-".to_string())
+}
 
-    let a = parts[0];
-    let b = parts[1];
-    if a.len() == 7 && &a[4..5] == "-" && b.len() == 7 && &b[4..5] == "-" {
-        Some((a.to_string(), b.to_string()))
+fn print_stats(s: &Stats) {
+    println!("== Statistics {} ==", fmt_months((s.start, s.end)));
+    println!("Savings rate: {}", fmt_opt(s.savings_rate, "%"));
+
+    println!();
+    println!(
+        "{:8} {:>10} {:>10} {:>8} {:>10} {:>10} {:>10}",
+        "month", "income", "expense", "savings", "avg 3m", "avg 6m", "avg 12m"
+    );
+    for m in &s.months {
+        let [r3, r6, r12] = m.rolling_expense.map(|v| v.map(|v| format!("{:.0}", v)).unwrap_or_else(|| "-".to_string()));
+        println!(
+            "{:8} {:>10} {:>10} {:>8} {:>10} {:>10} {:>10}",
+            m.month.to_string(),
+            m.income,
+            m.expense,
+            fmt_opt(m.savings_rate, "%"),
+            r3,
+            r6,
+            r12
+        );
+    }
+
+    println!();
+    println!("== Spending by category ==");
+    println!("{:12} {:>10} {:>10} {:>10} {:>10}  days since", "category", "total", "average", "median", "last");
+    for c in &s.categories {
+        println!(
+            "{:12} {:>10} {:>10.0} {:>10.0} {:>10}  {}",
+            c.category,
+            c.total,
+            c.average,
+            c.median,
+            c.last_entry.to_string(),
+            c.days_since_last
+        );
+    }
+
+    println!();
+    println!("== Largest expenses ==");
+    if s.largest.is_empty() {
+        println!("(none)");
+    }
+    for e in &s.largest {
+        println!("{}", fmt_entry(e));
+    }
+}
+
+fn print_category_totals(title: &str, rows: &[CategoryTotal], chart: bool) {
+    println!("== {} ==", title);
+    if rows.is_empty() {
+        println!("(no data)");
+    } else if chart {
+        let bars: Vec<(String, i64)> = rows.iter().map(|r| (r.category.clone(), r.total)).collect();
+        for line in chart::bars(&bars, chart::width()) {
+            println!("{}", line);
+        }
     } else {
-        None
+        for r in rows {
+            println!("{:12} {}", r.category, r.total);
+        }
+    }
+}
+
+fn print_spending_heatmap(conn: &Connection, from: NaiveDate, to: NaiveDate) {
+    let days = match period_summaries(conn, from, to, Granularity::Day) {
+        Ok(days) => days,
+        Err(e) => {
+            eprintln!("Failed to get daily totals: {}", e);
+            return;
+        }
+    };
+    let daily: BTreeMap<NaiveDate, i64> = days.iter().map(|d| (d.start, d.expense)).collect();
+    println!("== Daily spending {}..{} ==", from, to);
+    for line in chart::heatmap(&daily, from, to, chart::width()) {
+        println!("{}", line);
+    }
+}
+
+fn print_trend(conn: &Connection, period: &PeriodSettings, start: YearMonth, end: YearMonth) {
+    let months = match period_summaries(conn, period.month_start(start), period.month_end(end), Granularity::Month) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Failed to get monthly totals: {}", e);
+            return;
+        }
+    };
+    println!("== Monthly trend {}..{} ==", start, end);
+    let series: [(&str, Vec<i64>); 3] = [
+        ("income", months.iter().map(|m| m.income).collect()),
+        ("expense", months.iter().map(|m| m.expense).collect()),
+        ("balance", months.iter().map(|m| m.balance).collect()),
+    ];
+    for (name, values) in &series {
+        println!(
+            "{:8} {}  {}..{}",
+            name,
+            chart::sparkline(values),
+            values.iter().min().unwrap_or(&0),
+            values.iter().max().unwrap_or(&0)
+        );
     }
 }
 
-fn write_csv(path: &str, rows: &[Entry]) -> csv::Result<()> {
+// Builds the HTML report for `start..=end`; a single month gets a daily bar
+// chart, a range one bar pair per month.
+fn html_report(conn: &Connection, period: &PeriodSettings, start: YearMonth, end: YearMonth) -> Result<String, Box<dyn Error>> {
+    let s = summary_in_range(conn, start, end)?;
+    let expense = category_totals_by_kind_in_range(conn, start, end, Kind::Expense)?;
+    let income = category_totals_by_kind_in_range(conn, start, end, Kind::Income)?;
+    let entries = if start == end { entries_in_month(conn, start)? } else { entries_in_range(conn, start, end)? };
+    let (from, to) = (period.month_start(start), period.month_end(end));
+
+    let (bar_title, bar_labels, bar_series) = if start == end {
+        let days = period_summaries(conn, from, to, Granularity::Day)?;
+        (
+            "Daily expense".to_string(),
+            days.iter().map(|d| d.start.format("%-d").to_string()).collect(),
+            vec![html::BarSeries {
+                name: "Expense",
+                color: html::EXPENSE_COLOR,
+                values: days.iter().map(|d| d.expense).collect(),
+            }],
+        )
+    } else {
+        let months = period_summaries(conn, from, to, Granularity::Month)?;
+        (
+            "Income and expense by month".to_string(),
+            months.iter().map(|m| m.label.clone()).collect(),
+            vec![
+                html::BarSeries {
+                    name: "Income",
+                    color: html::INCOME_COLOR,
+                    values: months.iter().map(|m| m.income).collect(),
+                },
+                html::BarSeries {
+                    name: "Expense",
+                    color: html::EXPENSE_COLOR,
+                    values: months.iter().map(|m| m.expense).collect(),
+                },
+            ],
+        )
+    };
+
+    Ok(html::render(&html::Report {
+        title: format!("Household ledger {}", fmt_months((start, end))),
+        income: s.income,
+        expense: s.expense,
+        balance: s.balance,
+        expense_categories: &expense,
+        income_categories: &income,
+        bar_title,
+        bar_labels,
+        bar_series,
+        entries: &entries,
+    }))
+}
+
+fn pdf_statement(
+    conn: &Connection,
+    period: &PeriodSettings,
+    start: YearMonth,
+    end: YearMonth,
+    font: Option<&std::path::Path>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let s = summary_in_range(conn, start, end)?;
+    let expense = category_totals_by_kind_in_range(conn, start, end, Kind::Expense)?;
+    let income = category_totals_by_kind_in_range(conn, start, end, Kind::Income)?;
+    let entries = if start == end { entries_in_month(conn, start)? } else { entries_in_range(conn, start, end)? };
+    pdf::render(
+        &pdf::Statement {
+            title: format!("Statement {}", fmt_months((start, end))),
+            from: period.month_start(start),
+            to: period.month_end(end),
+            income: s.income,
+            expense: s.expense,
+            balance: s.balance,
+            expense_categories: &expense,
+            income_categories: &income,
+            entries: &entries,
+        },
+        font,
+    )
+}
+
+fn print_pivot(t: &PivotTable) {
+    let k = if t.kind == Kind::Expense { "Expense" } else { "Income" };
+    println!(
+        "== Category x Month ({}) {}..{} ==",
+        k,
+        t.months.first().map(|m| m.to_string()).unwrap_or_default(),
+        t.months.last().map(|m| m.to_string()).unwrap_or_default()
+    );
+    if t.rows.is_empty() {
+        println!("(no data)");
+        return;
+    }
+    let mut header = format!("{:12}", "category");
+    for m in &t.months {
+        header.push_str(&format!(" {:>9}", m.to_string()));
+    }
+    println!("{} {:>10} {:>10}", header, "total", "average");
+    for r in &t.rows {
+        let mut line = format!("{:12}", r.category);
+        for v in &r.values {
+            line.push_str(&format!(" {:>9}", v));
+        }
+        println!("{} {:>10} {:>10.0}", line, r.total, r.average);
+    }
+    let mut line = format!("{:12}", "total");
+    for v in &t.month_totals {
+        line.push_str(&format!(" {:>9}", v));
+    }
+    println!("{} {:>10} {:>10.0}", line, t.grand_total, t.month_average);
+}
+
+fn write_pivot_csv(path: &str, t: &PivotTable, enc: TextEncoding, lossy: bool) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path)?;
+    let mut buf = BufWriter::new(file);
+
+    buf.write_all(enc.bom())?;
+
+    let mut wtr = WriterBuilder::new().from_writer(buf);
+
+    let mut header = vec!["category".to_string()];
+    header.extend(t.months.iter().map(|m| m.to_string()));
+    header.push("total".to_string());
+    header.push("average".to_string());
+    wtr.write_record(encode_record(&header, enc, lossy, "header")?)?;
+
+    for r in &t.rows {
+        let mut rec = vec![r.category.clone()];
+        rec.extend(r.values.iter().map(|v| v.to_string()));
+        rec.push(r.total.to_string());
+        rec.push(format!("{:.0}", r.average));
+        wtr.write_record(encode_record(&rec, enc, lossy, &format!("category {}", r.category))?)?;
+    }
+    let mut rec = vec!["total".to_string()];
+    rec.extend(t.month_totals.iter().map(|v| v.to_string()));
+    rec.push(t.grand_total.to_string());
+    rec.push(format!("{:.0}", t.month_average));
+    wtr.write_record(encode_record(&rec, enc, lossy, "totals")?)?;
+
+    wtr.flush()?;
+    Ok(())
+}
+
+fn parse_ym_range(s: &str) -> Option<(YearMonth, YearMonth)> {
+    let (a, b) = s.split_once("..")?;
+    let start: YearMonth = a.parse().ok()?;
+    let end: YearMonth = b.parse().ok()?;
+    if start <= end { Some((start, end)) } else { None }
+}
+
+// `month [YYYY-MM] [filters]` or `range YYYY-MM..YYYY-MM [filters]`, as taken
+// by the export commands. `None` when the arguments do not have that shape.
+fn export_filter(args: &[String], period: &PeriodSettings) -> Result<Option<(YearMonth, YearMonth, EntryFilter)>, String> {
+    let (start, end, opts) = match (args.first().map(|s| s.as_str()), args.get(1)) {
+        (Some("month"), Some(m)) if !m.starts_with("--") => {
+            let ym: YearMonth = m.parse().map_err(|e: InvalidYearMonth| e.to_string())?;
+            (ym, ym, &args[2..])
+        }
+        (Some("month"), _) => (current_ym(period), current_ym(period), &args[1..]),
+        (Some("range"), Some(r)) => match parse_ym_range(r) {
+            Some((a, b)) => (a, b, &args[2..]),
+            None => return Err(format!("Invalid range: {} (expected YYYY-MM..YYYY-MM)", r)),
+        },
+        _ => return Ok(None),
+    };
+    let base = EntryFilter::new().months(start, end).order(SortOrder::DateAsc);
+    Ok(Some((start, end, parse_filter(opts, base)?)))
+}
+
+fn write_csv(
+    path: &str,
+    conn: &Connection,
+    filter: &EntryFilter,
+    enc: TextEncoding,
+    lossy: bool,
+) -> Result<usize, Box<dyn Error>> {
     let file = File::create(path)?;
     let mut buf = BufWriter::new(file);
 
-    buf.write_all(b"\xEF\xBB\xBF")?;
+    buf.write_all(enc.bom())?;
 
     let mut wtr = WriterBuilder::new().from_writer(buf);
 
-    wtr.write_record(&["id", "kind", "amount", "category", "note", "created_at"])?;
+    wtr.write_record(["id", "kind", "amount", "category", "note", "created_at", "account", "tags"])?;
 
-    for e in rows {
+    let mut count = 0;
+    for_each_entry(conn, filter, |e| {
         let kind = if e.kind == Kind::Expense { "expense" } else { "income" };
-        wtr.write_record(&[
+        count += 1;
+        let rec = [
             e.id.to_string(),
             kind.to_string(),
             e.amount.to_string(),
-            e.category.to_string(),
-            e.note.clone().unwrap_or_default(),
+            e.category,
+            e.note.unwrap_or_default(),
             e.created_at.to_string(),
-        ])?;
-    }
+            e.account.unwrap_or_default(),
+            e.tags.join(","),
+        ];
+        wtr.write_record(encode_record(&rec, enc, lossy, &format!("entry #{}", e.id))?)?;
+        Ok::<_, Box<dyn Error>>(())
+    })?;
     wtr.flush()?;
-    Ok(())
+    Ok(count)
 }
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
+    if is_encrypted(Path::new(DB_PATH)) && let Err(msg) = unlock_db() {
+        eprintln!("{}", msg);
+        return;
+    }
     let conn = open_db().expect("Failed to open database");
-    init_db(&conn).expect("Failed to initialize database");
+    let init = init_db(&conn);
+    // `doctor` still looks at a database that fails to migrate.
+    if args.get(1).is_some_and(|a| a == "doctor") {
+        run_doctor(&conn, &args, init.err());
+        return;
+    }
+    init.expect("Failed to initialize database");
+    let period = load_period_settings(&conn).expect("Failed to load settings");
 
     if args.len() < 2 {
-        eprintln!("Usage: {} <command> [add|list|delete|report ...]", args[0]);
+        eprintln!("Usage: {} <command> [add|edit|list|delete|restore|trash|undo|redo|history|log|recurring|subscriptions|report|import|export|backup|rekey|doctor|settings ...]", args[0]);
         return;
     }
 
@@ -67,112 +768,598 @@ fn main() {
         "add" => {
             if args.len() < 5 {
                 eprintln!(
-                    "Usage: {} add <expense|income> <amount> <category> [note...]",
+                    "Usage: {} add <expense|income> <amount> <category> [note...] [--account A] [--tag T]...",
                     args[0]
                 );
                 return;
             }
-            let kind = match args[2].as_str() {
-                "expense" => Kind::Expense,
-                "income" => Kind::Income,
+            let Some(kind) = parse_kind(&args[2]) else {
+                eprintln!("Invalid kind: {}. Use 'expense' or 'income'.", args[2]);
+                return;
+            };
+            let amount: i64 = match args[3].parse() {
+                Ok(num) if num > 0 => num,
                 _ => {
-                    eprintln!("Invalid kind: {}. Use 'expense' or 'income'.", args[2]);
+                    eprintln!("Invalid amount: {}", args[3]);
                     return;
                 }
             };
-            let amount: i64 = match args[3].parse() {
+            let mut entry = NewEntry::new(kind, amount, &args[4]);
+            let mut note_words = Vec::new();
+            let mut rest = args[5..].iter();
+            while let Some(a) = rest.next() {
+                match a.as_str() {
+                    "--account" | "--tag" => {
+                        let Some(v) = rest.next() else {
+                            eprintln!("Missing value for {}", a);
+                            return;
+                        };
+                        if a == "--account" {
+                            entry.account = Some(v.clone());
+                        } else {
+                            entry.tags.push(v.clone());
+                        }
+                    }
+                    _ => note_words.push(a.as_str()),
+                }
+            }
+            if !note_words.is_empty() {
+                entry.note = Some(note_words.join(" "));
+            }
+            insert_entry(&conn, &entry).expect("Failed to add entry");
+            println!("Entry added successfully.");
+        }
+
+        "edit" => {
+            if args.len() < 6 {
+                eprintln!(
+                    "Usage: {} edit <id> <expense|income> <amount> <category> [note...]",
+                    args[0]
+                );
+                return;
+            }
+            let id: i64 = match args[2].parse() {
+                Ok(num) => num,
+                Err(_) => {
+                    eprintln!("Invalid ID: {}", args[2]);
+                    return;
+                }
+            };
+            let Some(kind) = parse_kind(&args[3]) else {
+                eprintln!("Invalid kind: {}. Use 'expense' or 'income'.", args[3]);
+                return;
+            };
+            let amount: i64 = match args[4].parse() {
                 Ok(num) if num > 0 => num,
                 _ => {
-                    eprintln!("Invalid amount: {}", args[3]);
+                    eprintln!("Invalid amount: {}", args[4]);
                     return;
                 }
             };
-            let category = &args[4];
-            let note = if args.len() > 5 {
-                Some(args[5..].join(" "))
+            let category = &args[5];
+            let note = if args.len() > 6 {
+                Some(args[6..].join(" "))
             } else {
                 None
             };
-            add_entry(&conn, kind, amount, category, note.as_deref()).expect("Failed to add entry");
-            println!("Entry added successfully.");
+            match update_entry(&conn, id, kind, amount, category, note.as_deref()) {
+                Ok(rows) if rows > 0 => println!("Entry {} updated.", id),
+                Ok(_) => println!("No entry found with ID: {}", id),
+                Err(e) => eprintln!("Failed to update entry: {}", e),
+            }
         }
 
-        "list" => match list_entries(&conn) {
-            Ok(entries) => {
-                for entry in entries {
-                    let k = if entry.kind == Kind::Expense {
-                        "Expense"
-                    } else {
-                        "Income"
+        "undo" => match undo(&conn) {
+            Ok(Some(op)) => println!("Undid: {} ({} by {})", op.label, op.created_at, op.actor),
+            Ok(None) => println!("Nothing to undo."),
+            Err(e) => eprintln!("Failed to undo: {}", e),
+        },
+
+        "redo" => match redo(&conn) {
+            Ok(Some(op)) => println!("Redid: {} ({} by {})", op.label, op.created_at, op.actor),
+            Ok(None) => println!("Nothing to redo."),
+            Err(e) => eprintln!("Failed to redo: {}", e),
+        },
+
+        "history" => {
+            if args.len() < 3 {
+                eprintln!("Usage: {} history <id>", args[0]);
+                return;
+            }
+            let id: i64 = match args[2].parse() {
+                Ok(num) => num,
+                Err(_) => {
+                    eprintln!("Invalid ID: {}", args[2]);
+                    return;
+                }
+            };
+            match entry_history(&conn, id) {
+                Ok(rows) => {
+                    if rows.is_empty() {
+                        println!("No history for entry {}", id);
+                    }
+                    for r in &rows {
+                        print_audit_record(r);
+                    }
+                }
+                Err(e) => eprintln!("Failed to load history: {}", e),
+            }
+        }
+
+        "log" => {
+            let limit: i64 = match args.get(2).map(|v| v.parse()) {
+                None => 20,
+                Some(Ok(n)) if n > 0 => n,
+                Some(_) => {
+                    eprintln!("Usage: {} log [count]", args[0]);
+                    return;
+                }
+            };
+            match recent_changes(&conn, limit) {
+                Ok(rows) => {
+                    if rows.is_empty() {
+                        println!("(no changes recorded)");
+                    }
+                    for r in &rows {
+                        print_audit_record(r);
+                    }
+                }
+                Err(e) => eprintln!("Failed to load change log: {}", e),
+            }
+        }
+
+        "list" => {
+            let filter = match parse_filter(&args[2..], EntryFilter::new()) {
+                Ok(f) => f,
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    eprintln!("Usage: {} list {}", args[0], FILTER_USAGE);
+                    return;
+                }
+            };
+            let printed = for_each_entry(&conn, &filter, |entry| {
+                let k = if entry.kind == Kind::Expense {
+                    "Expense"
+                } else {
+                    "Income"
+                };
+                let mut extra = String::new();
+                if let Some(a) = &entry.account {
+                    extra.push_str(&format!(" @{}", a));
+                }
+                for t in &entry.tags {
+                    extra.push_str(&format!(" #{}", t));
+                }
+                println!(
+                    "{}: {} {} {} {} [{}]{}",
+                    entry.created_at,
+                    k,
+                    entry.amount,
+                    entry.category,
+                    entry.note.as_deref().unwrap_or(""),
+                    entry.id,
+                    extra
+                );
+                Ok::<_, ledger_module::Error>(())
+            });
+            if let Err(e) = printed {
+                eprintln!("Failed to list entries: {}", e);
+            }
+        }
+
+        "delete" => {
+            if args.len() < 3 {
+                eprintln!("Usage: {} delete <id>", args[0]);
+                return;
+            }
+            let id: i64 = match args[2].parse() {
+                Ok(num) => num,
+                Err(_) => {
+                    eprintln!("Invalid ID: {}", args[2]);
+                    return;
+                }
+            };
+            match delete_entry(&conn, id) {
+                Ok(rows) if rows > 0 => {
+                    println!("Entry {} moved to trash.", id);
+                    println!("Undo with: {} restore {}", args[0], id);
+                }
+                Ok(_) => println!("No entry found with ID: {}", id),
+                Err(e) => eprintln!("Failed to delete entry: {}", e),
+            }
+        }
+
+        "restore" => {
+            if args.len() < 3 {
+                eprintln!("Usage: {} restore <id>", args[0]);
+                return;
+            }
+            let id: i64 = match args[2].parse() {
+                Ok(num) => num,
+                Err(_) => {
+                    eprintln!("Invalid ID: {}", args[2]);
+                    eprintln!("To replace the database with a backup file, use: {} backup restore <path> --force", args[0]);
+                    return;
+                }
+            };
+            match restore_entry(&conn, id) {
+                Ok(rows) if rows > 0 => println!("Entry {} restored.", id),
+                Ok(_) => println!("No trashed entry found with ID: {}", id),
+                Err(e) => eprintln!("Failed to restore entry: {}", e),
+            }
+        }
+
+        "backup" => match args.get(2).map(|s| s.as_str()) {
+            None => match auto_backup(&conn, "manual") {
+                Ok(Some(path)) => println!("Backed up to {} (verified)", path.display()),
+                Ok(None) => eprintln!("Automatic backups are off (backup-keep is 0); give a path instead."),
+                Err(e) => eprintln!("Backup failed: {}", e),
+            },
+            Some("list") => {
+                let backups = list_backups(&conn);
+                if backups.is_empty() {
+                    println!("(no backups)");
+                }
+                for path in backups {
+                    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    println!("{}  {:>8} KB", path.display(), size.div_ceil(1024));
+                }
+            }
+            Some("verify") => {
+                let Some(path) = args.get(3) else {
+                    eprintln!("Usage: {} backup verify <path>", args[0]);
+                    return;
+                };
+                match verify_database(Path::new(path)) {
+                    Ok(problems) if problems.is_empty() => println!("{}: ok", path),
+                    Ok(problems) => {
+                        for p in problems {
+                            println!("{}: {}", path, p);
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to check {}: {}", path, e),
+                }
+            }
+            Some("restore") => {
+                let Some(path) = args[3..].iter().find(|a| !a.starts_with("--")) else {
+                    eprintln!("Usage: {} backup restore <path> --force", args[0]);
+                    return;
+                };
+                if !args.iter().any(|a| a == "--force") {
+                    eprintln!("This replaces all data with the contents of {}.", path);
+                    eprintln!("The current data is backed up first. Run again with --force to restore.");
+                    return;
+                }
+                restore_from_backup(&conn, Path::new(path));
+            }
+            Some(path) => {
+                let dest = Path::new(path);
+                if dest.exists() && !args.iter().any(|a| a == "--force") {
+                    eprintln!("{} already exists (use --force to overwrite)", path);
+                    return;
+                }
+                match backup_database(&conn, dest) {
+                    Ok(problems) if problems.is_empty() => println!("Backed up to {} (verified)", path),
+                    Ok(problems) => {
+                        eprintln!("Backup failed verification and was not written:");
+                        for p in problems {
+                            eprintln!("  {}", p);
+                        }
+                    }
+                    Err(e) => eprintln!("Backup failed: {}", e),
+                }
+            }
+        },
+
+        "rekey" => {
+            let remove = match args.get(2).map(|s| s.as_str()) {
+                None => false,
+                Some("--remove") => true,
+                Some(_) => {
+                    eprintln!("Usage: {} rekey [--remove]", args[0]);
+                    return;
+                }
+            };
+            if !ENCRYPTION_SUPPORTED {
+                eprintln!("This build has no encryption support (rebuild with `--features sqlcipher`).");
+                return;
+            }
+            let was_encrypted = is_encrypted(Path::new(DB_PATH));
+            let new = if remove {
+                None
+            } else {
+                let first = match read_passphrase("New passphrase: ") {
+                    Ok(p) => p,
+                    Err(msg) => {
+                        eprintln!("{}", msg);
+                        return;
+                    }
+                };
+                match read_passphrase("Repeat the new passphrase: ") {
+                    Ok(p) if p == first => Some(p),
+                    Ok(_) => {
+                        eprintln!("The passphrases do not match.");
+                        return;
+                    }
+                    Err(msg) => {
+                        eprintln!("{}", msg);
+                        return;
+                    }
+                }
+            };
+            let plaintext_backups = list_backups(&conn).iter().filter(|b| !is_encrypted(b)).count();
+            let dir = backup_dir(&conn).map(|d| d.display().to_string()).unwrap_or_default();
+            match change_passphrase(conn, new.as_deref()) {
+                Err(e) => eprintln!("Failed to change the passphrase: {}", e),
+                Ok(_) if remove => {
+                    println!("Removed the encryption of {}. Existing backups keep their passphrase.", DB_PATH)
+                }
+                Ok(_) if !was_encrypted => {
+                    println!("Encrypted {}.", DB_PATH);
+                    if plaintext_backups > 0 {
+                        println!(
+                            "{} backup(s) in {} are not encrypted; delete them once you have checked the encrypted database.",
+                            plaintext_backups, dir
+                        );
+                    }
+                }
+                Ok(_) => println!("Changed the passphrase of {}. Existing backups keep the previous one.", DB_PATH),
+            }
+        }
+
+        "trash" => {
+            if args.len() < 3 {
+                eprintln!("Usage: {} trash <list|purge --older-than <days>>", args[0]);
+                return;
+            }
+            match args[2].as_str() {
+                "list" => match list_trash(&conn) {
+                    Ok(rows) => {
+                        if rows.is_empty() {
+                            println!("(trash is empty)");
+                        }
+                        for t in rows {
+                            let e = t.entry;
+                            let k = if e.kind == Kind::Expense { "Expense" } else { "Income" };
+                            println!(
+                                "{}: {} {} {} {} [{}] (deleted {})",
+                                e.created_at,
+                                k,
+                                e.amount,
+                                e.category,
+                                e.note.as_deref().unwrap_or(""),
+                                e.id,
+                                t.deleted_at
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to list trash: {}", e),
+                },
+                "purge" => {
+                    let days = match args.iter().position(|a| a == "--older-than") {
+                        Some(i) => match args.get(i + 1).map(|v| v.parse::<i64>()) {
+                            Some(Ok(d)) if d >= 0 => d,
+                            _ => {
+                                eprintln!("Usage: {} trash purge --older-than <days>", args[0]);
+                                return;
+                            }
+                        },
+                        None => {
+                            eprintln!("Usage: {} trash purge --older-than <days>", args[0]);
+                            return;
+                        }
+                    };
+                    match purge_trash(&conn, days) {
+                        Ok(n) => println!("Purged {} entries from trash.", n),
+                        Err(e) => eprintln!("Failed to purge trash: {}", e),
+                    }
+                }
+                _ => eprintln!("Usage: {} trash <list|purge --older-than <days>>", args[0]),
+            }
+        }
+
+        "recurring" => {
+            let usage = || {
+                eprintln!("Usage: {} recurring <list|add|remove> ...", args[0]);
+                eprintln!(
+                    "  add <expense|income> <amount> <category> --day D [--every N] [--from YYYY-MM] [--until YYYY-MM] [note...]"
+                );
+                eprintln!("  remove <id>");
+            };
+            match args.get(2).map(|s| s.as_str()) {
+                None | Some("list") => match list_recurring(&conn) {
+                    Ok(rules) => {
+                        if rules.is_empty() {
+                            println!("(no recurring items)");
+                        }
+                        for r in &rules {
+                            print_rule(r);
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to list recurring items: {}", e),
+                },
+                Some("add") => {
+                    if args.len() < 6 {
+                        usage();
+                        return;
+                    }
+                    let Some(kind) = parse_kind(&args[3]) else {
+                        eprintln!("Invalid kind: {}. Use 'expense' or 'income'.", args[3]);
+                        return;
+                    };
+                    let amount: i64 = match args[4].parse() {
+                        Ok(num) if num > 0 => num,
+                        _ => {
+                            eprintln!("Invalid amount: {}", args[4]);
+                            return;
+                        }
+                    };
+                    let today = chrono::Local::now().date_naive();
+                    let mut rule = NewRecurring::new(kind, amount, &args[5], 0, YearMonth::of(today));
+                    let mut note_words = Vec::new();
+                    let mut rest = args[6..].iter();
+                    while let Some(a) = rest.next() {
+                        if !matches!(a.as_str(), "--day" | "--every" | "--from" | "--until") {
+                            note_words.push(a.as_str());
+                            continue;
+                        }
+                        let Some(v) = rest.next() else {
+                            eprintln!("Missing value for {}", a);
+                            return;
+                        };
+                        let ok = match a.as_str() {
+                            "--day" => v.parse().ok().filter(|d| (1..=31).contains(d)).map(|d| rule.day = d),
+                            "--every" => v.parse().ok().filter(|n| *n > 0).map(|n| rule.every_months = n),
+                            "--from" => v.parse().ok().map(|m| rule.start = m),
+                            _ => v.parse().ok().map(|m| rule.end = Some(m)),
+                        };
+                        if ok.is_none() {
+                            eprintln!("Invalid value for {}: {}", a, v);
+                            return;
+                        }
+                    }
+                    if rule.day == 0 {
+                        usage();
+                        return;
+                    }
+                    if !note_words.is_empty() {
+                        rule.note = Some(note_words.join(" "));
+                    }
+                    match add_recurring(&conn, &rule) {
+                        Ok(id) => println!("Recurring item {} added.", id),
+                        Err(e) => eprintln!("Failed to add recurring item: {}", e),
+                    }
+                }
+                Some("remove") => {
+                    let Some(id) = args.get(3).and_then(|v| v.parse::<i64>().ok()) else {
+                        usage();
+                        return;
                     };
-                    println!(
-                        "{}: {} {} {} {} [{}]",
-                        entry.created_at,
-                        k,
-                        entry.amount,
-                        entry.category,
-                        entry.note.as_deref().unwrap_or(""),
-                        entry.id
-                    );
+                    match remove_recurring(&conn, id) {
+                        Ok(n) if n > 0 => println!("Recurring item {} removed.", id),
+                        Ok(_) => println!("No recurring item found with ID: {}", id),
+                        Err(e) => eprintln!("Failed to remove recurring item: {}", e),
+                    }
                 }
+                _ => usage(),
             }
-            Err(e) => eprintln!("Failed to list entries: {}", e),
-        },
+        }
 
-        "delete" => {
-            if args.len() < 3 {
-                eprintln!("Usage: {} delete <id>", args[0]);
-                return;
-            }
-            let id: i64 = match args[2].parse() {
-                Ok(num) => num,
-                Err(_) => {
-                    eprintln!("Invalid ID: {}", args[2]);
+        "subscriptions" => {
+            let subs = match detect_subscriptions(&conn) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Failed to detect subscriptions: {}", e);
                     return;
                 }
             };
-            match delete_entry(&conn, id) {
-                Ok(rows) if rows > 0 => println!("Entry deleted successfully."),
-                Ok(_) => println!("No entry found with ID: {}", id),
-                Err(e) => eprintln!("Failed to delete entry: {}", e),
+            match args.get(2).map(|s| s.as_str()) {
+                None | Some("list") => {
+                    if subs.is_empty() {
+                        println!("(no subscriptions detected)");
+                        return;
+                    }
+                    println!(
+                        "{:>3} {:12} {:20} {:8} {:>8} {:>10} {:10}",
+                        "#", "category", "note", "interval", "amount", "per year", "last seen"
+                    );
+                    let mut total = 0;
+                    for (i, s) in subs.iter().enumerate() {
+                        let interval = if s.interval == Interval::Monthly { "monthly" } else { "yearly" };
+                        println!(
+                            "{:>3} {:12} {:20} {:8} {:>8} {:>10} {:10}{}",
+                            i + 1,
+                            s.category,
+                            s.note.as_deref().unwrap_or(""),
+                            interval,
+                            s.amount,
+                            s.annual_cost,
+                            s.last_seen.to_string(),
+                            if s.tracked { "  (recurring)" } else { "" }
+                        );
+                        total += s.annual_cost;
+                    }
+                    println!("Estimated annual cost: {}", total);
+                    println!("Track one as a recurring item with: {} subscriptions track <#>", args[0]);
+                }
+                Some("track") => {
+                    let Some(s) = args
+                        .get(3)
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .and_then(|n| n.checked_sub(1))
+                        .and_then(|i| subs.get(i))
+                    else {
+                        eprintln!("Usage: {} subscriptions track <#> (see '{} subscriptions list')", args[0], args[0]);
+                        return;
+                    };
+                    if s.tracked {
+                        println!("{} {} is already a recurring item.", s.category, s.amount);
+                        return;
+                    }
+                    match add_recurring(&conn, &s.to_recurring()) {
+                        Ok(id) => println!("Recurring item {} added for {} {}.", id, s.category, s.amount),
+                        Err(e) => eprintln!("Failed to add recurring item: {}", e),
+                    }
+                }
+                _ => eprintln!("Usage: {} subscriptions [list|track <#>]", args[0]),
             }
         }
 
         "report" => {
             if args.len() < 3 {
                 eprintln!(
-                    "Usage: {} report <month|category> [YYYY-MM] [--income|--expense|--both]",
+                    "Usage: {} report <month|category|range|compare|pivot|forecast|anomalies|stats|year|quarterly|weekly|daily|periods> ...",
                     args[0]
                 );
                 return;
             }
-            let ym = if args.len() >= 4 && !args[3].starts_with("--") {
-                args[3].clone()
-            } else {
-                current_ym()
-            };
-
             match args[2].as_str() {
-                "month" => match month_summary(&conn, &ym) {
-                    Ok(s) => {
-                        println!("== Summary {} ==", s.month);
-                        println!("Income : {}", s.income);
-                        println!("Expense: {}", s.expense);
-                        println!("Balance: {}", s.balance);
-                    }
-                    Err(e) => eprintln!("Failed to get month summary: {}", e),
-                },
+                "month" => {
+                    let ym = match month_arg(args.get(3), &period) {
+                        Ok(ym) => ym,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return;
+                        }
+                    };
+                    match month_summary(&conn, ym) {
+                        Ok(s) => {
+                            println!("== Summary {} ==", s.month);
+                            println!("Income : {}", s.income);
+                            println!("Expense: {}", s.expense);
+                            println!("Balance: {}", s.balance);
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to get month summary: {}", e);
+                            return;
+                        }
+                    }
+                    if args.iter().any(|a| a == "--chart") {
+                        let rows = category_totals_by_kind(&conn, ym, Kind::Expense).unwrap_or_default();
+                        println!();
+                        print_category_totals(&format!("Category Totals (Expense) {}", ym), &rows, true);
+                        println!();
+                        print_spending_heatmap(&conn, period.month_start(ym), period.month_end(ym));
+                    }
+                }
 
                 "category" => {
+                    let ym = match month_arg(args.get(3), &period) {
+                        Ok(ym) => ym,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return;
+                        }
+                    };
                     let flag = args
                         .iter()
                         .find(|a| a.starts_with("--"))
                         .map(|s| s.as_str());
                     match flag {
                         Some("--both") => {
-                            let exp = category_totals_by_kind(&conn, &ym, Kind::Expense)
+                            let exp = category_totals_by_kind(&conn, ym, Kind::Expense)
                                 .unwrap_or_default();
-                            let inc = category_totals_by_kind(&conn, &ym, Kind::Income)
+                            let inc = category_totals_by_kind(&conn, ym, Kind::Income)
                                 .unwrap_or_default();
 
                             println!("== Category Totals (Expense) {} ==", ym);
@@ -193,7 +1380,7 @@ fn main() {
                             }
                         }
                         Some("--income") => {
-                            let rows = category_totals_by_kind(&conn, &ym, Kind::Income)
+                            let rows = category_totals_by_kind(&conn, ym, Kind::Income)
                                 .unwrap_or_default();
                             println!("== Category Totals (Income) {} ==", ym);
                             if rows.is_empty() {
@@ -204,7 +1391,7 @@ fn main() {
                             }
                         }
                         _ => {
-                            let rows = category_totals_by_kind(&conn, &ym, Kind::Expense)
+                            let rows = category_totals_by_kind(&conn, ym, Kind::Expense)
                                 .unwrap_or_default();
                             println!("== Category Totals (Expense) {} ==", ym);
                             if rows.is_empty() {
@@ -218,7 +1405,7 @@ fn main() {
                 }
                 "range" => {
                     if args.len() < 4 {
-                        eprintln!("Usage: {} report range <YYYY-MM..YYYY-MM> [--income|--expense|--both]", args[0]);
+                        eprintln!("Usage: {} report range <YYYY-MM..YYYY-MM> [--income|--expense|--both] [--chart]", args[0]);
                         return;
                     }
                     let range = &args[3];
@@ -227,9 +1414,13 @@ fn main() {
                         return;
                     };
 
-                    let flag = args.iter().find(|a| a.starts_with("--")).map(|s| s.as_str());
+                    let flag = args
+                        .iter()
+                        .find(|a| matches!(a.as_str(), "--income" | "--expense" | "--both"))
+                        .map(|s| s.as_str());
+                    let chart = args.iter().any(|a| a == "--chart");
 
-                    match summary_in_range(&conn, &start_ym, &end_ym) {
+                    match summary_in_range(&conn, start_ym, end_ym) {
                         Ok(s) => {
                             println!("== Summary {}..{} ==", s.start_month, s.end_month);
                             println!("Income : {}", s.income);
@@ -241,73 +1432,464 @@ fn main() {
                             return;
                         }
                     }
+                    if chart {
+                        println!();
+                        print_trend(&conn, &period, start_ym, end_ym);
+                    }
 
+                    let totals = |kind| category_totals_by_kind_in_range(&conn, start_ym, end_ym, kind).unwrap_or_default();
                     match flag {
                         Some("--both") => {
-                            let exp = category_totals_by_kind_in_range(&conn, &start_ym, &end_ym, Kind::Expense)
-                                .unwrap_or_default();
-                            let inc = category_totals_by_kind_in_range(&conn, &start_ym, &end_ym, Kind::Income)
-                                .unwrap_or_default();
-
                             println!();
-                            println!("== Category Totals (Expense) {}..{} ==", start_ym, end_ym);
-                            if exp.is_empty() { println!("(no data)"); }
-                            for r in exp { println!("{:12} {}", r.category, r.total); }
-
+                            print_category_totals(
+                                &format!("Category Totals (Expense) {}..{}", start_ym, end_ym),
+                                &totals(Kind::Expense),
+                                chart,
+                            );
                             println!();
-                            println!("== Category Totals (Income)  {}..{} ==", start_ym, end_ym);
-                            if inc.is_empty() { println!("(no data)"); }
-                            for r in inc { println!("{:12} {}", r.category, r.total); }
+                            print_category_totals(
+                                &format!("Category Totals (Income)  {}..{}", start_ym, end_ym),
+                                &totals(Kind::Income),
+                                chart,
+                            );
                         }
                         Some("--income") => {
-                            let rows = category_totals_by_kind_in_range(&conn, &start_ym, &end_ym, Kind::Income)
-                                .unwrap_or_default();
                             println!();
-                            println!("== Category Totals (Income) {}..{} ==", start_ym, end_ym);
-                            if rows.is_empty() { println!("(no data)"); }
-                            for r in rows { println!("{:12} {}", r.category, r.total); }
+                            print_category_totals(
+                                &format!("Category Totals (Income) {}..{}", start_ym, end_ym),
+                                &totals(Kind::Income),
+                                chart,
+                            );
                         }
                         _ => {
-                            let rows = category_totals_by_kind_in_range(&conn, &start_ym, &end_ym, Kind::Expense)
-                                .unwrap_or_default();
                             println!();
-                            println!("== Category Totals (Expense) {}..{} ==", start_ym, end_ym);
-                            if rows.is_empty() { println!("(no data)"); }
-                            for r in rows { println!("{:12} {}", r.category, r.total); }
+                            print_category_totals(
+                                &format!("Category Totals (Expense) {}..{}", start_ym, end_ym),
+                                &totals(Kind::Expense),
+                                chart,
+                            );
+                        }
+                    }
+                    if chart {
+                        println!();
+                        print_spending_heatmap(&conn, period.month_start(start_ym), period.month_end(end_ym));
+                    }
+                }
+                "year" | "quarterly" => {
+                    let year = match args.get(3) {
+                        Some(y) => match y.parse::<i32>() {
+                            Ok(y) if YearMonth::new(y, 1).is_some() => y,
+                            _ => {
+                                eprintln!("Invalid year: {} (expected 0000-9999)", y);
+                                return;
+                            }
+                        },
+                        None => period.fiscal_year_of(current_ym(&period)),
+                    };
+                    let g = if args[2] == "year" { Granularity::Month } else { Granularity::Quarter };
+                    let (first, last) = period.fiscal_year_months(year);
+                    let from = period.month_start(first);
+                    let to = period.month_end(last);
+                    let title = if period.is_calendar_year() { year.to_string() } else { format!("FY{}", year) };
+                    match period_summaries(&conn, from, to, g) {
+                        Ok(rows) => print_periods(&title, &rows),
+                        Err(e) => eprintln!("Failed to get period summary: {}", e),
+                    }
+                }
+                "weekly" | "daily" => {
+                    let (start, end) = match args.get(3) {
+                        None => (current_ym(&period), current_ym(&period)),
+                        Some(a) if a.contains("..") => match parse_ym_range(a) {
+                            Some(r) => r,
+                            None => {
+                                eprintln!("Invalid range: {} (expected YYYY-MM..YYYY-MM)", a);
+                                return;
+                            }
+                        },
+                        Some(a) => match a.parse::<YearMonth>() {
+                            Ok(ym) => (ym, ym),
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                return;
+                            }
+                        },
+                    };
+                    let g = if args[2] == "weekly" { Granularity::Week } else { Granularity::Day };
+                    match period_summaries(&conn, period.month_start(start), period.month_end(end), g) {
+                        Ok(rows) => print_periods(&format!("{}..{}", start, end), &rows),
+                        Err(e) => eprintln!("Failed to get period summary: {}", e),
+                    }
+                }
+                "compare" => {
+                    let positional: Vec<&String> = args[3..].iter().filter(|a| !a.starts_with("--")).collect();
+                    let current = match positional.first() {
+                        Some(a) => parse_months_arg(a),
+                        None => Some((current_ym(&period), current_ym(&period))),
+                    };
+                    let Some(current) = current else {
+                        eprintln!(
+                            "Usage: {} report compare <YYYY-MM[..YYYY-MM]> [YYYY-MM[..YYYY-MM]] [--yoy]",
+                            args[0]
+                        );
+                        return;
+                    };
+                    let previous = match positional.get(1) {
+                        Some(a) => match parse_months_arg(a) {
+                            Some(p) => p,
+                            None => {
+                                eprintln!("Invalid period: {}", a);
+                                return;
+                            }
+                        },
+                        None if args.iter().any(|a| a == "--yoy") => {
+                            (current.0.add_months(-12), current.1.add_months(-12))
+                        }
+                        None => {
+                            let len = current.0.through(current.1).len() as i32;
+                            (current.0.add_months(-len), current.0.pred())
+                        }
+                    };
+                    match compare_periods(&conn, current, previous) {
+                        Ok(c) => print_comparison(&c),
+                        Err(e) => eprintln!("Failed to compare periods: {}", e),
+                    }
+                }
+                "pivot" => {
+                    let Some((start_ym, end_ym)) = args.get(3).and_then(|r| parse_ym_range(r)) else {
+                        eprintln!("Usage: {} report pivot <YYYY-MM..YYYY-MM> [--income|--expense]", args[0]);
+                        return;
+                    };
+                    let kind = if args.iter().any(|a| a == "--income") { Kind::Income } else { Kind::Expense };
+                    match category_month_pivot(&conn, start_ym, end_ym, kind) {
+                        Ok(t) => print_pivot(&t),
+                        Err(e) => eprintln!("Failed to build pivot: {}", e),
+                    }
+                }
+                "forecast" => {
+                    let mut months = 6;
+                    let mut history = 3;
+                    let mut rest = args[3..].iter();
+                    while let Some(a) = rest.next() {
+                        let (target, range) = match a.as_str() {
+                            "--months" => (&mut months, FORECAST_MONTHS),
+                            "--history" => (&mut history, FORECAST_HISTORY_MONTHS),
+                            _ => {
+                                eprintln!("Usage: {} report forecast [--months N] [--history N]", args[0]);
+                                return;
+                            }
+                        };
+                        match rest.next().map(|v| v.parse::<u32>()) {
+                            Some(Ok(n)) if range.contains(&n) => *target = n,
+                            _ => {
+                                eprintln!("Invalid value for {} (expected {} to {})", a, range.start(), range.end());
+                                return;
+                            }
+                        }
+                    }
+                    match forecast(&conn, chrono::Local::now().date_naive(), months, history) {
+                        Ok(f) => print_forecast(&f),
+                        Err(e) => eprintln!("Failed to build forecast: {}", e),
+                    }
+                }
+                "anomalies" => {
+                    let range = match args.get(3) {
+                        Some(a) => parse_months_arg(a),
+                        None => Some((current_ym(&period), current_ym(&period))),
+                    };
+                    let Some(range) = range else {
+                        eprintln!("Usage: {} report anomalies [YYYY-MM[..YYYY-MM]]", args[0]);
+                        return;
+                    };
+                    match find_anomalies(&conn, range.0, range.1) {
+                        Ok(a) => print_anomalies(&fmt_months(range), &a),
+                        Err(e) => eprintln!("Failed to look for anomalies: {}", e),
+                    }
+                }
+                "stats" => {
+                    let range = match args.get(3) {
+                        Some(a) => parse_months_arg(a),
+                        None => Some((current_ym(&period).add_months(-11), current_ym(&period))),
+                    };
+                    let Some((start, end)) = range else {
+                        eprintln!("Usage: {} report stats [YYYY-MM[..YYYY-MM]] (default: last 12 months)", args[0]);
+                        return;
+                    };
+                    let as_of = chrono::Local::now().date_naive().min(period.month_end(end));
+                    match stats(&conn, start, end, as_of) {
+                        Ok(s) => print_stats(&s),
+                        Err(e) => eprintln!("Failed to compute statistics: {}", e),
+                    }
+                }
+                "periods" => {
+                    let usage = || {
+                        eprintln!(
+                            "Usage: {} report periods <day|week|month|quarter|year> <YYYY-MM-DD..YYYY-MM-DD>",
+                            args[0]
+                        )
+                    };
+                    let (Some(g), Some(range)) = (args.get(3), args.get(4)) else {
+                        usage();
+                        return;
+                    };
+                    let g: Granularity = match g.parse() {
+                        Ok(g) => g,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return;
+                        }
+                    };
+                    let Some((from, to)) = range.split_once("..") else {
+                        usage();
+                        return;
+                    };
+                    let (from, to) = match (parse_date(from), parse_date(to)) {
+                        (Ok(a), Ok(b)) if a <= b => (a, b),
+                        _ => {
+                            eprintln!("Invalid range: {} (expected YYYY-MM-DD..YYYY-MM-DD)", range);
+                            return;
                         }
+                    };
+                    match period_summaries(&conn, from, to, g) {
+                        Ok(rows) => print_periods(range, &rows),
+                        Err(e) => eprintln!("Failed to get period summary: {}", e),
                     }
                 }
                 _ => {
                     eprintln!(
-                        "Unknown report type: {}. Use 'month' or 'category'.",
+                        "Unknown report type: {}. Use 'month', 'category', 'range', 'compare', 'pivot', 'forecast', 'anomalies', 'stats', 'year', 'quarterly', 'weekly', 'daily' or 'periods'.",
                         args[2]
                     );
                 }
             }
         }
+        "import" => {
+            let (enc, _, args) = match take_encoding_args(&args) {
+                Ok(v) => v,
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    return;
+                }
+            };
+            let usage = || {
+                eprintln!("Usage: {} import <csv|zaim|moneyforward|qif|camt053|mt940> <file> [--dry-run] [--encoding E]", args[0]);
+                eprintln!("  zaim, moneyforward, qif: [--subcategory tag|join]; qif: [--day-first] [--round]");
+                eprintln!("  camt053, mt940: [--category C] [--account A] [--round]");
+                eprintln!("Encodings: {} (default: utf-8, or shift_jis if the file is not valid UTF-8)", ENCODING_NAMES);
+            };
+            let mut dry_run = false;
+            let mut day_first = false;
+            let mut bank = import::BankOptions { category: "未分類".to_string(), account: None, round: false };
+            let mut subcategory = import::Subcategory::Tag;
+            let mut positional = Vec::new();
+            let mut rest = args[2..].iter();
+            while let Some(a) = rest.next() {
+                match a.as_str() {
+                    "--dry-run" => dry_run = true,
+                    "--day-first" => day_first = true,
+                    "--round" => bank.round = true,
+                    "--category" | "--account" => {
+                        let Some(v) = rest.next() else {
+                            usage();
+                            return;
+                        };
+                        if a == "--category" {
+                            bank.category = v.clone();
+                        } else {
+                            bank.account = Some(v.clone());
+                        }
+                    }
+                    "--subcategory" => match rest.next().map(|s| s.as_str()) {
+                        Some("tag") => subcategory = import::Subcategory::Tag,
+                        Some("join") => subcategory = import::Subcategory::Join,
+                        _ => {
+                            usage();
+                            return;
+                        }
+                    },
+                    _ => positional.push(a.as_str()),
+                }
+            }
+            let [format, path] = positional[..] else {
+                usage();
+                return;
+            };
+            let bytes = match std::fs::read(path) {
+                Ok(b) => b,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {}", path, e);
+                    return;
+                }
+            };
+            let enc = enc.unwrap_or_else(|| TextEncoding::detect(&bytes));
+            let text = match enc.decode(&bytes) {
+                Ok(t) => t,
+                Err(e) => {
+                    eprintln!("Failed to read {}: {}", path, e);
+                    return;
+                }
+            };
+            let failed = |errors: Vec<String>| {
+                for e in &errors {
+                    eprintln!("{}", e);
+                }
+                eprintln!("Nothing imported ({} problem(s)).", errors.len());
+            };
+            let label = format!("import {}", path);
+            let backed_up = || match auto_backup(&conn, "pre-import") {
+                Ok(_) => true,
+                Err(e) => {
+                    eprintln!("Nothing imported: backup failed: {}", e);
+                    false
+                }
+            };
+            if format == "csv" {
+                let entries = match import::read_csv(&text) {
+                    Ok(v) => v,
+                    Err(errors) => {
+                        failed(errors);
+                        return;
+                    }
+                };
+                if dry_run {
+                    for e in &entries {
+                        print_import_row("new ", e);
+                    }
+                    println!("Would import {} entries from {} ({})", entries.len(), path, enc);
+                    return;
+                }
+                if !backed_up() {
+                    return;
+                }
+                let imported = run_operation(&conn, &label, |tx| {
+                    for e in &entries {
+                        insert_entry(tx, e)?;
+                    }
+                    Ok(entries.len())
+                });
+                let n = match imported {
+                    Ok(n) => n,
+                    Err(e) => {
+                        eprintln!("Failed to import entries: {}", e);
+                        return;
+                    }
+                };
+                println!("Imported {} entries from {} ({})", n, path, enc);
+                return;
+            }
+            let (source, parsed) = match format {
+                "zaim" => ("zaim", import::read_zaim(&text, subcategory)),
+                "moneyforward" | "mf" => ("moneyforward", import::read_moneyforward(&text, subcategory)),
+                "qif" => ("qif", qif::read_qif(&text, subcategory, day_first, bank.round)),
+                "camt053" | "camt" => ("bank", import::read_camt053(&text, &bank)),
+                "mt940" => ("bank", import::read_mt940(&text, &bank)),
+                _ => {
+                    usage();
+                    return;
+                }
+            };
+            let parsed = match parsed {
+                Ok(p) => p,
+                Err(errors) => {
+                    failed(errors);
+                    return;
+                }
+            };
+            let skipped = |repeats: usize| {
+                format!(
+                    "skipped {} already imported, {} transfers, {} excluded",
+                    repeats, parsed.transfers, parsed.excluded
+                )
+            };
+            if dry_run {
+                let known = match already_imported(&conn, source, &parsed.items) {
+                    Ok(k) => k,
+                    Err(e) => {
+                        eprintln!("Failed to check imported rows: {}", e);
+                        return;
+                    }
+                };
+                for (item, known) in parsed.items.iter().zip(&known) {
+                    print_import_row(if *known { "skip" } else { "new " }, &item.entry);
+                }
+                let repeats = known.iter().filter(|k| **k).count();
+                println!(
+                    "Would import {} entries from {} ({}); {}",
+                    parsed.items.len() - repeats,
+                    path,
+                    enc,
+                    skipped(repeats)
+                );
+                return;
+            }
+            if !backed_up() {
+                return;
+            }
+            let r = match import_entries(&conn, source, &label, &parsed.items) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("Failed to import entries: {}", e);
+                    return;
+                }
+            };
+            println!("Imported {} entries from {} ({}); {}", r.added, path, enc, skipped(r.skipped));
+        }
         "export" => {
             if args.len() < 3 {
-                eprintln!("Usage: {} export <csv> <month|range> [YYYY-MM|YYYY-MM..YYYY-MM]", args[0]);
+                eprintln!("Usage: {} export <csv|html|pdf|qif|xlsx> <month|range> [YYYY-MM|YYYY-MM..YYYY-MM]", args[0]);
                 return;
             }
             match args[2].as_str() {
                 "csv" => {
+                    let (enc, lossy, args) = match take_encoding_args(&args) {
+                        Ok((enc, lossy, rest)) => (enc.unwrap_or(TextEncoding::Utf8Bom), lossy, rest),
+                        Err(msg) => {
+                            eprintln!("{}", msg);
+                            return;
+                        }
+                    };
                     if args.len() < 4 {
-                        eprintln!("Usage: {} export csv <month [YYYY-MM]|range YYYY-MM..YYYY-MM>", args[0]);
+                        eprintln!(
+                            "Usage: {} export csv <month [YYYY-MM]|range YYYY-MM..YYYY-MM> [filters] [--encoding E] [--lossy]",
+                            args[0]
+                        );
+                        eprintln!("Filters: {}", FILTER_USAGE);
+                        eprintln!("Encodings: {} (default utf-8-bom)", ENCODING_NAMES);
                         return;
                     }
                     match args[3].as_str() {
                         "month" => {
                             // 5番目に YYYY-MM があれば使い、無ければ当月
-                            let ym = if args.len() >= 5 { args[4].clone() } else { current_ym() };
-                            let rows = entries_in_month(&conn, &ym).expect("fetch month entries");
+                            let (ym, opts) = if args.len() >= 5 && !args[4].starts_with("--") {
+                                match args[4].parse::<YearMonth>() {
+                                    Ok(ym) => (ym, &args[5..]),
+                                    Err(e) => {
+                                        eprintln!("{}", e);
+                                        return;
+                                    }
+                                }
+                            } else {
+                                (current_ym(&period), &args[4..])
+                            };
+                            let base = EntryFilter::new().month(ym).order(SortOrder::DateAsc);
+                            let filter = match parse_filter(opts, base) {
+                                Ok(f) => f,
+                                Err(msg) => {
+                                    eprintln!("{}", msg);
+                                    return;
+                                }
+                            };
                             let filename = format!("export_month_{}.csv", ym);
-                            write_csv(&filename, &rows).expect("write csv");
-                            println!("Exported to {}", filename);
+                            match write_csv(&filename, &conn, &filter, enc, lossy) {
+                                Ok(n) => println!("Exported {} entries to {} ({})", n, filename, enc),
+                                Err(e) => {
+                                    let _ = std::fs::remove_file(&filename);
+                                    eprintln!("Failed to export CSV: {}", e);
+                                }
+                            }
                         }
                         "range" => {
                             if args.len() < 5 {
-                                eprintln!("Usage: {} export csv range <YYYY-MM..YYYY-MM>", args[0]);
+                                eprintln!("Usage: {} export csv range <YYYY-MM..YYYY-MM> [filters] [--encoding E] [--lossy]", args[0]);
                                 return;
                             }
                             let range = &args[4];
@@ -315,17 +1897,235 @@ fn main() {
                                 eprintln!("Invalid range: {} (expected YYYY-MM..YYYY-MM)", range);
                                 return;
                             };
-                            let rows = entries_in_range(&conn, &start_ym, &end_ym).expect("fetch range entries");
+                            let base = EntryFilter::new().months(start_ym, end_ym).order(SortOrder::DateAsc);
+                            let filter = match parse_filter(&args[5..], base) {
+                                Ok(f) => f,
+                                Err(msg) => {
+                                    eprintln!("{}", msg);
+                                    return;
+                                }
+                            };
                             let filename = format!("export_range_{}..{}.csv", start_ym, end_ym);
-                            write_csv(&filename, &rows).expect("write csv");
-                                println!("Exported to {}", filename);
+                            match write_csv(&filename, &conn, &filter, enc, lossy) {
+                                Ok(n) => println!("Exported {} entries to {} ({})", n, filename, enc),
+                                Err(e) => {
+                                    let _ = std::fs::remove_file(&filename);
+                                    eprintln!("Failed to export CSV: {}", e);
+                                }
+                            }
                         }
+                        "pivot" => {
+                            let Some((start_ym, end_ym)) = args.get(4).and_then(|r| parse_ym_range(r)) else {
+                                eprintln!("Usage: {} export csv pivot <YYYY-MM..YYYY-MM> [--income] [--encoding E] [--lossy]", args[0]);
+                                return;
+                            };
+                            let kind = if args.iter().any(|a| a == "--income") { Kind::Income } else { Kind::Expense };
+                            let table = match category_month_pivot(&conn, start_ym, end_ym, kind) {
+                                Ok(t) => t,
+                                Err(e) => {
+                                    eprintln!("Failed to build pivot: {}", e);
+                                    return;
+                                }
+                            };
+                            let filename = format!("export_pivot_{}..{}.csv", start_ym, end_ym);
+                            match write_pivot_csv(&filename, &table, enc, lossy) {
+                                Ok(()) => println!("Exported to {} ({})", filename, enc),
+                                Err(e) => {
+                                    let _ = std::fs::remove_file(&filename);
+                                    eprintln!("Failed to export CSV: {}", e);
+                                }
+                            }
+                        }
+                        _ => {
+                            eprintln!("Usage: {} export csv <month|range|pivot> ...", args[0]);
+                        }
+                    }
+                }
+                "html" => {
+                    let range = match (args.get(3).map(|s| s.as_str()), args.get(4)) {
+                        (Some("month"), None) => Some((current_ym(&period), current_ym(&period))),
+                        (Some("month"), Some(m)) => m.parse().ok().map(|ym| (ym, ym)),
+                        (Some("range"), Some(r)) => parse_ym_range(r),
+                        _ => None,
+                    };
+                    let Some((start_ym, end_ym)) = range else {
+                        eprintln!("Usage: {} export html <month [YYYY-MM]|range YYYY-MM..YYYY-MM>", args[0]);
+                        return;
+                    };
+                    let filename = if start_ym == end_ym {
+                        format!("report_month_{}.html", start_ym)
+                    } else {
+                        format!("report_range_{}..{}.html", start_ym, end_ym)
+                    };
+                    match html_report(&conn, &period, start_ym, end_ym)
+                        .and_then(|page| Ok(std::fs::write(&filename, page)?))
+                    {
+                        Ok(()) => println!("Exported to {}", filename),
+                        Err(e) => eprintln!("Failed to export HTML: {}", e),
+                    }
+                }
+                "pdf" => {
+                    let font = args.iter().position(|a| a == "--font").and_then(|i| args.get(i + 1));
+                    let positional: Vec<&String> = args[3..]
+                        .iter()
+                        .filter(|a| !a.starts_with("--") && Some(*a) != font)
+                        .collect();
+                    let range = match (positional.first().map(|s| s.as_str()), positional.get(1)) {
+                        (Some("month"), None) => Some((current_ym(&period), current_ym(&period))),
+                        (Some("month"), Some(m)) => m.parse().ok().map(|ym| (ym, ym)),
+                        (Some("range"), Some(r)) => parse_ym_range(r),
+                        _ => None,
+                    };
+                    let Some((start_ym, end_ym)) = range else {
+                        eprintln!(
+                            "Usage: {} export pdf <month [YYYY-MM]|range YYYY-MM..YYYY-MM> [--font FILE.ttf]",
+                            args[0]
+                        );
+                        return;
+                    };
+                    let filename = if start_ym == end_ym {
+                        format!("statement_month_{}.pdf", start_ym)
+                    } else {
+                        format!("statement_range_{}..{}.pdf", start_ym, end_ym)
+                    };
+                    match pdf_statement(&conn, &period, start_ym, end_ym, font.map(std::path::Path::new))
+                        .and_then(|bytes| Ok(std::fs::write(&filename, bytes)?))
+                    {
+                        Ok(()) => println!("Exported to {}", filename),
+                        Err(e) => eprintln!("Failed to export PDF: {}", e),
+                    }
+                }
+                "qif" => {
+                    let (enc, lossy, args) = match take_encoding_args(&args) {
+                        Ok((enc, lossy, rest)) => (enc.unwrap_or(TextEncoding::Utf8), lossy, rest),
+                        Err(msg) => {
+                            eprintln!("{}", msg);
+                            return;
+                        }
+                    };
+                    let (start_ym, end_ym, filter) = match export_filter(&args[3..], &period) {
+                        Ok(Some(v)) => v,
+                        Ok(None) => {
+                            eprintln!(
+                                "Usage: {} export qif <month [YYYY-MM]|range YYYY-MM..YYYY-MM> [filters] [--encoding E] [--lossy]",
+                                args[0]
+                            );
+                            eprintln!("Filters: {}", FILTER_USAGE);
+                            return;
+                        }
+                        Err(msg) => {
+                            eprintln!("{}", msg);
+                            return;
+                        }
+                    };
+                    let filename = if start_ym == end_ym {
+                        format!("export_month_{}.qif", start_ym)
+                    } else {
+                        format!("export_range_{}..{}.qif", start_ym, end_ym)
+                    };
+                    let result = query_entries(&conn, &filter).map_err(Box::<dyn Error>::from).and_then(|entries| {
+                        let mut buf = BufWriter::new(File::create(&filename)?);
+                        qif::write_qif(&mut buf, &entries, enc, lossy)?;
+                        buf.flush()?;
+                        Ok(entries.len())
+                    });
+                    match result {
+                        Ok(n) => println!("Exported {} entries to {} ({})", n, filename, enc),
+                        Err(e) => {
+                            let _ = std::fs::remove_file(&filename);
+                            eprintln!("Failed to export QIF: {}", e);
+                        }
+                    }
+                }
+                "xlsx" => {
+                    let usage = || {
+                        eprintln!("Usage: {} export xlsx <month [YYYY-MM]|range YYYY-MM..YYYY-MM> [filters]", args[0]);
+                        eprintln!("Filters: {}", FILTER_USAGE);
+                    };
+                    let (start_ym, end_ym, filter) = match export_filter(&args[3..], &period) {
+                        Ok(Some(v)) => v,
+                        Ok(None) => {
+                            usage();
+                            return;
+                        }
+                        Err(msg) => {
+                            eprintln!("{}", msg);
+                            return;
+                        }
+                    };
+                    let mut months = Vec::new();
+                    let mut ym = start_ym;
+                    while ym <= end_ym {
+                        months.push(xlsx::SummaryMonth {
+                            month: ym,
+                            from: period.month_start(ym),
+                            until: period.month_start(ym.succ()),
+                        });
+                        ym = ym.succ();
+                    }
+                    let filename = if start_ym == end_ym {
+                        format!("export_month_{}.xlsx", start_ym)
+                    } else {
+                        format!("export_range_{}..{}.xlsx", start_ym, end_ym)
+                    };
+                    match xlsx::write_xlsx(&filename, &conn, &filter, &months) {
+                        Ok(n) => println!("Exported {} entries to {}", n, filename),
+                        Err(e) => eprintln!("Failed to export xlsx: {}", e),
+                    }
+                }
+                _ => eprintln!("Usage: {} export <csv|html|pdf|qif|xlsx> ...", args[0]),
+            }
+        }
+        "settings" => {
+            let usage = || {
+                eprintln!(
+                    "Usage: {} settings [show|set <fiscal-year-start 1-12|month-start-day 1-31|backup-keep N|backup-keep-days N> <value>]",
+                    args[0]
+                )
+            };
+            match args.get(2).map(|s| s.as_str()) {
+                None | Some("show") => {
+                    println!("fiscal-year-start: {}", period.fiscal_year_start_month);
+                    println!("month-start-day  : {}", period.month_start_day);
+                    let retention = load_backup_retention(&conn);
+                    println!("backup-keep      : {}", retention.keep);
+                    println!("backup-keep-days : {}", retention.keep_days);
+                }
+                Some("set") => {
+                    let (Some(key), Some(value)) = (args.get(3), args.get(4)) else {
+                        usage();
+                        return;
+                    };
+                    if let ("backup-keep" | "backup-keep-days", Ok(n)) = (key.as_str(), value.parse::<u32>()) {
+                        let mut retention = load_backup_retention(&conn);
+                        if key == "backup-keep" {
+                            retention.keep = n;
+                        } else {
+                            retention.keep_days = n;
+                        }
+                        match save_backup_retention(&conn, &retention) {
+                            Ok(()) => println!("Set {} to {}.", key, value),
+                            Err(e) => eprintln!("Failed to save settings: {}", e),
+                        }
+                        return;
+                    }
+                    let mut updated = period;
+                    match (key.as_str(), value.parse::<u32>()) {
+                        ("fiscal-year-start", Ok(m)) if (1..=12).contains(&m) => {
+                            updated.fiscal_year_start_month = m
+                        }
+                        ("month-start-day", Ok(d)) if (1..=31).contains(&d) => updated.month_start_day = d,
                         _ => {
-                            eprintln!("Usage: {} export csv <month|range> ...", args[0]);
+                            usage();
+                            return;
                         }
                     }
+                    match save_period_settings(&conn, &updated) {
+                        Ok(()) => println!("Set {} to {}.", key, value),
+                        Err(e) => eprintln!("Failed to save settings: {}", e),
+                    }
                 }
-                _ => eprintln!("Usage: {} export <csv> ...", args[0]),
+                _ => usage(),
             }
         }
         _ => {
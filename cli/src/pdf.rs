@@ -0,0 +1,269 @@
+// Paginated PDF statement (A4, portrait).
+
+use std::cell::Cell;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use ledger_module::{CategoryTotal, Entry, Kind};
+use printpdf::{BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, Point};
+
+use crate::fmt_amount as amount;
+
+const PAGE_W: f32 = 210.0;
+const PAGE_H: f32 = 297.0;
+const LEFT: f32 = 20.0;
+const RIGHT: f32 = PAGE_W - 20.0;
+const TOP: f32 = PAGE_H - 20.0;
+const BOTTOM: f32 = 25.0;
+const BODY: f32 = 9.0;
+const ROW: f32 = 5.0;
+// Width of one Courier character per point of font size, in mm.
+const MONO_EM_MM: f32 = 0.6 * 25.4 / 72.0;
+// Characters of a category / note that fit on one line of the entry table.
+const CATEGORY_CHARS: usize = 18;
+const NOTE_CHARS: usize = 48;
+
+pub struct Statement<'a> {
+    pub title: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub income: i64,
+    pub expense: i64,
+    pub balance: i64,
+    pub expense_categories: &'a [CategoryTotal],
+    pub income_categories: &'a [CategoryTotal],
+    pub entries: &'a [Entry],
+}
+
+#[derive(Copy, Clone)]
+enum Face {
+    Regular,
+    Bold,
+    Mono,
+}
+
+enum Op {
+    Text { x: f32, y: f32, size: f32, face: Face, text: String },
+    Rule { y: f32 },
+}
+
+// Lays content out top to bottom, starting a new page when it runs out of room.
+struct Layout {
+    pages: Vec<Vec<Op>>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout { pages: vec![Vec::new()], y: TOP }
+    }
+
+    /// Starts a new page unless `height` mm still fit. Returns whether it did.
+    fn reserve(&mut self, height: f32) -> bool {
+        if self.y - height >= BOTTOM {
+            return false;
+        }
+        self.pages.push(Vec::new());
+        self.y = TOP;
+        true
+    }
+
+    fn text(&mut self, x: f32, size: f32, face: Face, text: impl Into<String>) {
+        let y = self.y;
+        self.pages.last_mut().expect("at least one page").push(Op::Text {
+            x,
+            y,
+            size,
+            face,
+            text: text.into(),
+        });
+    }
+
+    // Right-aligned number at `right`, in the monospaced face so it can be measured.
+    fn number(&mut self, right: f32, size: f32, value: String) {
+        let x = right - value.chars().count() as f32 * size * MONO_EM_MM;
+        self.text(x, size, Face::Mono, value);
+    }
+
+    fn rule(&mut self) {
+        let y = self.y + 1.5;
+        self.pages.last_mut().expect("at least one page").push(Op::Rule { y });
+    }
+
+    fn advance(&mut self, height: f32) {
+        self.y -= height;
+    }
+}
+
+/// Renders the statement. Without a TrueType/OpenType `font` the builtin PDF
+/// fonts are used and characters outside ASCII print as `?`, with a warning on
+/// stderr; pass a font to print other scripts such as Japanese.
+pub fn render(s: &Statement, font: Option<&Path>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut l = Layout::new();
+    let latin_only = font.is_none();
+    let replaced = Cell::new(0usize);
+    let text = |t: &str| -> String {
+        if latin_only {
+            replaced.set(replaced.get() + t.chars().filter(|c| !c.is_ascii()).count());
+            t.chars().map(|c| if c.is_ascii() { c } else { '?' }).collect()
+        } else {
+            t.to_string()
+        }
+    };
+
+    l.text(LEFT, 18.0, Face::Bold, text(&s.title));
+    l.advance(8.0);
+    l.text(LEFT, BODY, Face::Regular, format!("Period: {} to {}", s.from, s.to));
+    l.advance(ROW);
+    l.text(
+        LEFT,
+        BODY,
+        Face::Regular,
+        format!("Issued: {}", chrono::Local::now().format("%Y-%m-%d")),
+    );
+    l.advance(ROW * 2.0);
+
+    for (label, value) in [("Income", s.income), ("Expense", s.expense), ("Balance", s.balance)] {
+        l.text(LEFT, 11.0, Face::Bold, label);
+        l.number(LEFT + 70.0, 11.0, amount(value));
+        l.advance(6.0);
+    }
+    l.advance(ROW);
+
+    for (title, rows) in [("Expense by category", s.expense_categories), ("Income by category", s.income_categories)] {
+        l.reserve(ROW * 4.0);
+        l.text(LEFT, 12.0, Face::Bold, title);
+        l.advance(ROW + 1.0);
+        let total: i64 = rows.iter().map(|r| r.total).sum();
+        if rows.is_empty() {
+            l.text(LEFT, BODY, Face::Regular, "(no data)");
+            l.advance(ROW);
+        }
+        for r in rows {
+            l.reserve(ROW);
+            l.text(LEFT, BODY, Face::Regular, text(&r.category));
+            l.number(LEFT + 100.0, BODY, amount(r.total));
+            l.number(LEFT + 125.0, BODY, format!("{:.1}%", r.total as f64 * 100.0 / total as f64));
+            l.advance(ROW);
+        }
+        l.advance(ROW);
+    }
+
+    let entry_header = |l: &mut Layout| {
+        l.text(LEFT, BODY, Face::Bold, "Date");
+        l.text(LEFT + 24.0, BODY, Face::Bold, "Category");
+        l.text(LEFT + 58.0, BODY, Face::Bold, "Note");
+        l.text(RIGHT - 20.0, BODY, Face::Bold, "Amount");
+        l.advance(ROW);
+        l.rule();
+    };
+    l.reserve(ROW * 4.0);
+    l.text(LEFT, 12.0, Face::Bold, "Entries");
+    l.advance(ROW + 1.0);
+    entry_header(&mut l);
+    if s.entries.is_empty() {
+        l.text(LEFT, BODY, Face::Regular, "(no entries)");
+        l.advance(ROW);
+    }
+    for e in s.entries {
+        let note_lines = wrap(&text(e.note.as_deref().unwrap_or("")), NOTE_CHARS);
+        if l.reserve(ROW * note_lines.len().max(1) as f32) {
+            entry_header(&mut l);
+        }
+        let signed = if e.kind == Kind::Expense { -e.amount } else { e.amount };
+        l.text(LEFT, BODY, Face::Regular, e.created_at.format("%Y-%m-%d").to_string());
+        l.text(LEFT + 24.0, BODY, Face::Regular, text(&e.category).chars().take(CATEGORY_CHARS).collect::<String>());
+        l.number(RIGHT, BODY, amount(signed));
+        for (i, line) in note_lines.iter().enumerate() {
+            if i > 0 {
+                l.advance(ROW);
+            }
+            l.text(LEFT + 58.0, BODY, Face::Regular, line.clone());
+        }
+        l.advance(ROW);
+    }
+
+    if replaced.get() > 0 {
+        eprintln!(
+            "{} non-ASCII character(s) were printed as '?'; pass --font <file.ttf> with a font that has them.",
+            replaced.get()
+        );
+    }
+    draw(s, l.pages, font)
+}
+
+fn draw(s: &Statement, pages: Vec<Vec<Op>>, font: Option<&Path>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (doc, first_page, first_layer) = PdfDocument::new(&s.title, Mm(PAGE_W), Mm(PAGE_H), "Layer 1");
+    let (regular, bold): (IndirectFontRef, IndirectFontRef) = match font {
+        Some(path) => {
+            let f = doc.add_external_font(File::open(path)?)?;
+            (f.clone(), f)
+        }
+        None => (
+            doc.add_builtin_font(BuiltinFont::Helvetica)?,
+            doc.add_builtin_font(BuiltinFont::HelveticaBold)?,
+        ),
+    };
+    let mono = doc.add_builtin_font(BuiltinFont::Courier)?;
+
+    let count = pages.len();
+    for (i, ops) in pages.into_iter().enumerate() {
+        let (page, layer) = if i == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(PAGE_W), Mm(PAGE_H), "Layer 1")
+        };
+        let layer = doc.get_page(page).get_layer(layer);
+        for op in ops {
+            match op {
+                Op::Text { x, y, size, face, text } => {
+                    let f = match face {
+                        Face::Regular => &regular,
+                        Face::Bold => &bold,
+                        Face::Mono => &mono,
+                    };
+                    layer.use_text(text, size, Mm(x), Mm(y), f);
+                }
+                Op::Rule { y } => layer.add_line(Line {
+                    points: vec![(Point::new(Mm(LEFT), Mm(y)), false), (Point::new(Mm(RIGHT), Mm(y)), false)],
+                    is_closed: false,
+                }),
+            }
+        }
+        let footer = format!("Page {} of {}", i + 1, count);
+        layer.use_text(&s.title, 8.0, Mm(LEFT), Mm(12.0), &regular);
+        layer.use_text(&footer, 8.0, Mm(RIGHT - 22.0), Mm(12.0), &regular);
+    }
+    Ok(doc.save_to_bytes()?)
+}
+
+// Splits `s` into lines of at most `width` characters, at spaces where possible.
+fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in s.split_whitespace() {
+        let mut word = word.to_string();
+        while word.chars().count() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            let head: String = word.chars().take(width).collect();
+            word = word.chars().skip(width).collect();
+            lines.push(head);
+        }
+        let needed = current.chars().count() + word.chars().count() + usize::from(!current.is_empty());
+        if needed > width && !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
@@ -0,0 +1,289 @@
+// Quicken Interchange Format. Only bank-style sections (!Type:Bank, CCard,
+// Cash, Oth A, Oth L) are read; investment and list sections are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::Write;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use ledger_module::{Entry, ImportItem, Kind, NewEntry};
+
+use crate::encoding::{TextEncoding, encode_record};
+use crate::import::{AppImport, Subcategory, categorize, join_note, whole_units};
+
+const BANK_TYPES: [&str; 5] = ["bank", "ccard", "cash", "oth a", "oth l"];
+
+#[derive(Default)]
+struct Split {
+    category: String,
+    memo: String,
+    amount: Option<String>,
+}
+
+#[derive(Default)]
+struct Record {
+    line: usize,
+    date: String,
+    amount: String,
+    payee: String,
+    memo: String,
+    category: String,
+    splits: Vec<Split>,
+}
+
+enum Section {
+    None,
+    Account,
+    Transactions,
+    Other,
+}
+
+/// Parses QIF text. Dates are month first (`MM/DD/YYYY`, `MM/DD'YY`) unless
+/// `day_first`; ISO dates are always accepted. Each split line becomes its
+/// own entry. Transfers (`L[Account]`) are skipped. Amounts with cents are
+/// rejected unless `round`.
+pub fn read_qif(text: &str, mode: Subcategory, day_first: bool, round: bool) -> Result<AppImport, Vec<String>> {
+    let mut out = AppImport { items: Vec::new(), transfers: 0, excluded: 0 };
+    let mut errors = Vec::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut section = Section::None;
+    let mut account: Option<String> = None;
+    let mut pending_account: Option<String> = None;
+    let mut rec = Record::default();
+
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('!') {
+            let header = line.trim().to_ascii_lowercase();
+            if header == "!account" {
+                section = Section::Account;
+            } else if let Some(t) = header.strip_prefix("!type:") {
+                section = if BANK_TYPES.contains(&t.trim()) { Section::Transactions } else { Section::Other };
+            }
+            // !Option / !Clear lines do not change the section.
+            continue;
+        }
+        let mut chars = line.chars();
+        let code = chars.next().unwrap_or('^');
+        let value = chars.as_str().trim().to_string();
+        match section {
+            Section::Account => match code {
+                'N' => pending_account = Some(value),
+                '^' => account = pending_account.take(),
+                _ => {}
+            },
+            Section::Transactions => {
+                if rec.line == 0 {
+                    rec.line = i + 1;
+                }
+                match code {
+                    'D' => rec.date = value,
+                    'T' => rec.amount = value,
+                    'U' if rec.amount.is_empty() => rec.amount = value,
+                    'P' => rec.payee = value,
+                    'M' => rec.memo = value,
+                    'L' => rec.category = value,
+                    'S' => rec.splits.push(Split { category: value, ..Split::default() }),
+                    'E' => {
+                        if let Some(s) = rec.splits.last_mut() {
+                            s.memo = value;
+                        }
+                    }
+                    '$' => {
+                        if let Some(s) = rec.splits.last_mut() {
+                            s.amount = Some(value);
+                        }
+                    }
+                    '^' => {
+                        let done = std::mem::take(&mut rec);
+                        if let Err(e) = finish(done, account.as_deref(), mode, day_first, round, &mut out, &mut occurrences) {
+                            errors.push(e);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Section::None | Section::Other => {}
+        }
+    }
+    if rec.line != 0 {
+        errors.push(format!("line {}: transaction not terminated with ^", rec.line));
+    }
+    if errors.is_empty() { Ok(out) } else { Err(errors) }
+}
+
+fn finish(
+    rec: Record,
+    account: Option<&str>,
+    mode: Subcategory,
+    day_first: bool,
+    round: bool,
+    out: &mut AppImport,
+    occurrences: &mut HashMap<String, usize>,
+) -> Result<(), String> {
+    let created_at = parse_date(&rec.date, day_first)
+        .ok_or_else(|| format!("line {}: invalid date '{}'", rec.line, rec.date))?;
+    let fingerprint = format!(
+        "{}|{}|{}|{}|{}|{}",
+        account.unwrap_or(""),
+        rec.date,
+        rec.amount,
+        rec.payee,
+        rec.memo,
+        rec.category
+    );
+    let n = occurrences.entry(fingerprint.clone()).or_default();
+    *n += 1;
+    let reference = format!("{}#{}", fingerprint, n);
+
+    let parts: Vec<(String, String, String)> = if rec.splits.is_empty() {
+        vec![(rec.category.clone(), rec.memo.clone(), rec.amount.clone())]
+    } else {
+        rec.splits
+            .iter()
+            .map(|s| {
+                let memo = if s.memo.is_empty() { rec.memo.clone() } else { s.memo.clone() };
+                (s.category.clone(), memo, s.amount.clone().unwrap_or_default())
+            })
+            .collect()
+    };
+    for (i, (category, memo, amount)) in parts.into_iter().enumerate() {
+        if category.starts_with('[') {
+            out.transfers += 1;
+            continue;
+        }
+        let signed = whole_units(&amount, '.', round).ok_or_else(|| {
+            format!("line {}: invalid amount '{}' (amounts must be whole; see --round)", rec.line, amount)
+        })?;
+        if signed == 0 {
+            out.excluded += 1;
+            continue;
+        }
+        let amount = signed.checked_abs().ok_or_else(|| format!("line {}: invalid amount '{}'", rec.line, amount))?;
+        let kind = if signed < 0 { Kind::Expense } else { Kind::Income };
+        let mut entry = NewEntry::new(kind, amount, "");
+        // Category:Subcategory/Class
+        let (path, class) = category.split_once('/').unwrap_or((&category, ""));
+        let (top, sub) = path.split_once(':').unwrap_or((path, ""));
+        categorize(&mut entry, top.trim(), sub.trim(), mode);
+        if !class.trim().is_empty() {
+            entry.tags.push(class.trim().to_string());
+        }
+        entry.created_at = Some(created_at);
+        entry.note = join_note(&[&rec.payee, &memo]);
+        entry.account = account.map(str::to_string);
+        let reference = if rec.splits.is_empty() { reference.clone() } else { format!("{}/{}", reference, i + 1) };
+        out.items.push(ImportItem { entry, reference });
+    }
+    Ok(())
+}
+
+fn parse_date(s: &str, day_first: bool) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    for f in ["%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(d) = NaiveDate::parse_from_str(s, f) {
+            return d.and_hms_opt(0, 0, 0);
+        }
+    }
+    // 1/5/2026, 01/05'26, 1-5-26
+    let parts: Vec<&str> = s.split(['/', '\'', '-', '.']).map(str::trim).collect();
+    let [a, b, y] = parts[..] else {
+        return None;
+    };
+    let (a, b, y): (u32, u32, i32) = (a.parse().ok()?, b.parse().ok()?, y.parse().ok()?);
+    let year = match y {
+        0..=69 => 2000 + y,
+        70..=99 => 1900 + y,
+        _ => y,
+    };
+    let (month, day) = if day_first { (b, a) } else { (a, b) };
+    NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(0, 0, 0)
+}
+
+/// Writes `entries` as QIF bank transactions, one account section per
+/// account. Categories written as `top/sub` by the app importers become
+/// `top:sub`, since `/` separates the class in QIF.
+pub fn write_qif<W: Write>(w: &mut W, entries: &[Entry], enc: TextEncoding, lossy: bool) -> Result<(), Box<dyn Error>> {
+    let mut by_account: BTreeMap<Option<&str>, Vec<&Entry>> = BTreeMap::new();
+    for e in entries {
+        by_account.entry(e.account.as_deref()).or_default().push(e);
+    }
+    let one_line = |s: &str| s.replace(['\r', '\n'], " ");
+    for (account, entries) in by_account {
+        let mut header = String::new();
+        if let Some(a) = account {
+            header.push_str(&format!("!Account\nN{}\nTBank\n^\n", one_line(a)));
+        }
+        header.push_str("!Type:Bank\n");
+        let what = format!("account {}", account.unwrap_or("-"));
+        for chunk in encode_record(&[header], enc, lossy, &what)? {
+            w.write_all(&chunk)?;
+        }
+        for e in entries {
+            let signed = if e.kind == Kind::Expense { -e.amount } else { e.amount };
+            let mut rec = format!("D{}\nT{}\n", e.created_at.format("%m/%d/%Y"), signed);
+            if let Some(note) = e.note.as_deref().filter(|n| !n.is_empty()) {
+                rec.push_str(&format!("M{}\n", one_line(note)));
+            }
+            rec.push_str(&format!("L{}\n^\n", one_line(&e.category).replace('/', ":")));
+            for chunk in encode_record(&[rec], enc, lossy, &format!("entry #{}", e.id))? {
+                w.write_all(&chunk)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quicken_file_is_imported() {
+        let text = include_str!("../tests/fixtures/statement.qif");
+        let r = read_qif(text, Subcategory::Tag, false, false).unwrap();
+        let summary: Vec<(Kind, i64, &str, Option<&str>)> = r
+            .items
+            .iter()
+            .map(|i| (i.entry.kind, i.entry.amount, i.entry.category.as_str(), i.entry.note.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Kind::Expense, 1500, "Food", Some("Super Market / Weekly shop")),
+                // Two splits of one record.
+                (Kind::Expense, 1000, "Food", Some("Drugstore / Bread")),
+                (Kind::Expense, 2000, "Health", Some("Drugstore")),
+                (Kind::Income, 200000, "Salary", Some("Employer")),
+            ]
+        );
+        assert_eq!(r.items[0].entry.tags, ["Groceries", "Household"]);
+        assert_eq!(r.items[2].entry.tags, ["Pharmacy"]);
+        assert_eq!(r.transfers, 1);
+        assert!(r.items.iter().all(|i| i.entry.account.as_deref() == Some("Checking")));
+        let dates: Vec<String> =
+            r.items.iter().map(|i| i.entry.created_at.unwrap().format("%Y-%m-%d").to_string()).collect();
+        assert_eq!(dates, ["2026-09-14", "2026-09-15", "2026-09-15", "2026-09-20"]);
+
+        let joined = read_qif(text, Subcategory::Join, false, false).unwrap();
+        assert_eq!(joined.items[2].entry.category, "Health/Pharmacy");
+    }
+
+    #[test]
+    fn bad_records_are_reported_by_line() {
+        let errors = read_qif("!Type:Bank\nD13/45/2026\nT-10\n^\nD09/01/2026\nT-1.50\n^\n", Subcategory::Tag, false, false)
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("line 2:"), "{:?}", errors);
+        assert!(errors[1].starts_with("line 5:"), "{:?}", errors);
+
+        let errors = read_qif("!Type:Bank\nD09/01/2026\nT-9223372036854775808\n^\n", Subcategory::Tag, false, false)
+            .err()
+            .unwrap();
+        assert_eq!(errors, ["line 2: invalid amount '-9223372036854775808'"]);
+    }
+}
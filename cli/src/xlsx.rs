@@ -0,0 +1,120 @@
+// Excel workbook export. The Summary and Categories sheets are formulas over
+// the Entries sheet, so they follow any edits made in Excel.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use ledger_module::{Connection, EntryFilter, Kind, YearMonth, for_each_entry};
+use rust_xlsxwriter::{Format, FormatAlign, Workbook, Worksheet, XlsxError};
+
+const AMOUNT_FORMAT: &str = "#,##0";
+
+// Entries sheet columns referenced by the formulas.
+const DATE_COL: &str = "Entries!$B:$B";
+const KIND_COL: &str = "Entries!$C:$C";
+const CATEGORY_COL: &str = "Entries!$D:$D";
+const AMOUNT_COL: &str = "Entries!$H:$H";
+
+/// One row of the Summary sheet: a month and its bounds (`until` exclusive).
+pub struct SummaryMonth {
+    pub month: YearMonth,
+    pub from: NaiveDate,
+    pub until: NaiveDate,
+}
+
+/// Writes the entries matching `filter` and returns how many there were.
+pub fn write_xlsx(
+    path: &str,
+    conn: &Connection,
+    filter: &EntryFilter,
+    months: &[SummaryMonth],
+) -> Result<usize, Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+    let header = Format::new().set_bold().set_border_bottom(rust_xlsxwriter::FormatBorder::Thin);
+    let amount = Format::new().set_num_format(AMOUNT_FORMAT);
+    let total_amount = Format::new().set_num_format(AMOUNT_FORMAT).set_bold();
+    let datetime = Format::new().set_num_format("yyyy-mm-dd hh:mm").set_align(FormatAlign::Left);
+    let date = Format::new().set_num_format("yyyy-mm-dd").set_align(FormatAlign::Left);
+    let percent = Format::new().set_num_format("0.0%");
+
+    let mut categories = BTreeSet::new();
+    let entries = workbook.add_worksheet().set_name("Entries")?;
+    write_header(entries, &header, &["ID", "Date", "Kind", "Category", "Note", "Account", "Tags", "Amount"])?;
+    let mut count = 0;
+    for_each_entry(conn, filter, |e| {
+        count += 1;
+        let row = count as u32;
+        let kind = if e.kind == Kind::Expense { "expense" } else { "income" };
+        entries.write_number(row, 0, e.id as f64)?;
+        entries.write_datetime_with_format(row, 1, e.created_at, &datetime)?;
+        entries.write_string(row, 2, kind)?;
+        entries.write_string(row, 3, &e.category)?;
+        entries.write_string(row, 4, e.note.as_deref().unwrap_or(""))?;
+        entries.write_string(row, 5, e.account.as_deref().unwrap_or(""))?;
+        entries.write_string(row, 6, e.tags.join(","))?;
+        entries.write_number_with_format(row, 7, e.amount as f64, &amount)?;
+        categories.insert((kind, e.category));
+        Ok::<_, Box<dyn Error>>(())
+    })?;
+    entries.set_freeze_panes(1, 0)?;
+    entries.autofilter(0, 0, count as u32, 7)?;
+    for (col, width) in [(0, 6), (1, 17), (2, 9), (3, 16), (4, 32), (5, 12), (6, 16), (7, 12)] {
+        entries.set_column_width(col, width)?;
+    }
+
+    let summary = workbook.add_worksheet().set_name("Summary")?;
+    write_header(summary, &header, &["Month", "From", "Until", "Income", "Expense", "Balance"])?;
+    for (i, m) in months.iter().enumerate() {
+        let row = i as u32 + 1;
+        let r = row + 1; // 1-based row in formulas
+        let sum = |kind: &str| {
+            format!(
+                "=SUMIFS({AMOUNT_COL},{KIND_COL},\"{kind}\",{DATE_COL},\">=\"&B{r},{DATE_COL},\"<\"&C{r})"
+            )
+        };
+        summary.write_string(row, 0, m.month.to_string())?;
+        summary.write_date_with_format(row, 1, m.from, &date)?;
+        summary.write_date_with_format(row, 2, m.until, &date)?;
+        summary.write_formula_with_format(row, 3, sum("income").as_str(), &amount)?;
+        summary.write_formula_with_format(row, 4, sum("expense").as_str(), &amount)?;
+        summary.write_formula_with_format(row, 5, format!("=D{r}-E{r}").as_str(), &amount)?;
+    }
+    let total_row = months.len() as u32 + 1;
+    summary.write_string_with_format(total_row, 0, "Total", &header)?;
+    for (col, letter) in [(3, 'D'), (4, 'E'), (5, 'F')] {
+        let formula = format!("=SUM({letter}2:{letter}{})", total_row);
+        summary.write_formula_with_format(total_row, col, formula.as_str(), &total_amount)?;
+    }
+    summary.set_column_width(0, 10)?;
+    for col in 1..=5 {
+        summary.set_column_width(col, 13)?;
+    }
+
+    let cats = workbook.add_worksheet().set_name("Categories")?;
+    write_header(cats, &header, &["Kind", "Category", "Total", "Share"])?;
+    for (i, (kind, category)) in categories.iter().enumerate() {
+        let row = i as u32 + 1;
+        let r = row + 1;
+        cats.write_string(row, 0, *kind)?;
+        cats.write_string(row, 1, category)?;
+        let total = format!("=SUMIFS({AMOUNT_COL},{KIND_COL},A{r},{CATEGORY_COL},B{r})");
+        cats.write_formula_with_format(row, 2, total.as_str(), &amount)?;
+        let share = format!("=IFERROR(C{r}/SUMIF($A:$A,A{r},$C:$C),0)");
+        cats.write_formula_with_format(row, 3, share.as_str(), &percent)?;
+    }
+    cats.set_freeze_panes(1, 0)?;
+    for (col, width) in [(0, 9), (1, 18), (2, 13), (3, 9)] {
+        cats.set_column_width(col, width)?;
+    }
+
+    workbook.save(path)?;
+    Ok(count)
+}
+
+fn write_header(sheet: &mut Worksheet, format: &Format, titles: &[&str]) -> Result<(), XlsxError> {
+    for (col, t) in titles.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *t, format)?;
+    }
+    Ok(())
+}
@@ -1,26 +1,124 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use ledger_module::{add_entry, category_totals_by_kind, delete_entry, init_db, list_entries, open_db, month_summary, Kind};
+use ledger_module::{add_entry, category_totals_by_kind, delete_entry, init_db, list_trash, query_entries, Entry, EntryCursor, EntryFilter, PAGE_SIZES, SortOrder, open_db, month_summary, redo, restore_entry, undo, Kind};
+use chrono::{NaiveDate, NaiveDateTime};
+use ledger_module::{
+    category_month_pivot, compare_periods, find_anomalies, forecast, load_period_settings, Delta, period_summaries, save_period_settings, Granularity, PeriodSettings, YearMonth,
+};
+use ledger_module::{is_encrypted, is_unlocked, unlock, DB_PATH, ENCRYPTION_SUPPORTED};
 use tauri::{Manager, WindowEvent};
 
+#[derive(serde::Deserialize, Default)]
+struct ListFilter {
+    from: Option<String>,
+    to: Option<String>,
+    kind: Option<String>,
+    categories: Option<Vec<String>>,
+    min_amount: Option<i64>,
+    max_amount: Option<i64>,
+    note: Option<String>,
+    account: Option<String>,
+    tags: Option<Vec<String>>,
+    sort: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl ListFilter {
+    fn into_filter(self) -> Result<EntryFilter, String> {
+        let mut f = EntryFilter::new();
+        f.from_date = self.from.as_deref().map(parse_date).transpose()?;
+        f.to_date = self.to.as_deref().map(parse_date).transpose()?;
+        match self.kind.as_deref() {
+            None => {}
+            Some("expense") => f = f.kind(Kind::Expense),
+            Some("income") => f = f.kind(Kind::Income),
+            Some(_) => return Err("kind must be 'expense' or 'income'".into()),
+        }
+        f.categories = self.categories.unwrap_or_default();
+        f.min_amount = self.min_amount;
+        f.max_amount = self.max_amount;
+        f.note_contains = self.note;
+        f.account = self.account;
+        f.tags = self.tags.unwrap_or_default();
+        f.order = match self.sort.as_deref() {
+            None | Some("-date") => SortOrder::DateDesc,
+            Some("date") => SortOrder::DateAsc,
+            Some("amount") => SortOrder::AmountAsc,
+            Some("-amount") => SortOrder::AmountDesc,
+            Some(_) => return Err("sort must be one of date, -date, amount, -amount".into()),
+        };
+        f.limit = self.limit;
+        f.offset = self.offset;
+        Ok(f)
+    }
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| format!("invalid date: {} (expected YYYY-MM-DD)", s))
+}
+
 #[tauri::command]
-fn list() -> Result<Vec<serde_json::Value>, String> {
+fn list(filter: Option<ListFilter>) -> Result<Vec<serde_json::Value>, String> {
+    let filter = filter.unwrap_or_default().into_filter()?;
     let conn = open_db().map_err(|e| e.to_string())?;
     init_db(&conn).map_err(|e| e.to_string())?;
-    let entries = list_entries(&conn).map_err(|e| e.to_string())?;
+    let entries = query_entries(&conn, &filter).map_err(|e| e.to_string())?;
 
-    Ok(entries.into_iter().map(|e| {
-        serde_json::json!({
-            "id": e.id,
-            "kind": if e.kind == Kind::Expense { "expense" } else { "income" },
-            "amount": e.amount,
-            "category": e.category,
-            "note": e.note,
-            "created_at": e.created_at,
-        })
+    Ok(entries.into_iter().map(entry_json).collect())
+}
+
+fn entry_json(e: Entry) -> serde_json::Value {
+    serde_json::json!({
+        "id": e.id,
+        "kind": if e.kind == Kind::Expense { "expense" } else { "income" },
+        "amount": e.amount,
+        "category": e.category,
+        "note": e.note,
+        "created_at": e.created_at.to_string(),
+        "account": e.account,
+        "tags": e.tags,
     })
-    .collect())
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Cursor {
+    created_at: String,
+    amount: i64,
+    id: i64,
+}
+
+#[tauri::command]
+fn list_page(
+    filter: Option<ListFilter>,
+    cursor: Option<Cursor>,
+    page_size: Option<i64>,
+) -> Result<serde_json::Value, String> {
+    let page_size = page_size.unwrap_or(100);
+    if !PAGE_SIZES.contains(&page_size) {
+        return Err(format!("page_size must be {}-{}", PAGE_SIZES.start(), PAGE_SIZES.end()));
+    }
+    let filter = filter.unwrap_or_default().into_filter()?;
+    let after = match cursor {
+        Some(c) => Some(EntryCursor {
+            created_at: NaiveDateTime::parse_from_str(&c.created_at, "%Y-%m-%d %H:%M:%S")
+                .map_err(|e| e.to_string())?,
+            amount: c.amount,
+            id: c.id,
+        }),
+        None => None,
+    };
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let page = ledger_module::list_page(&conn, &filter, after.as_ref(), page_size)
+        .map_err(|e| e.to_string())?;
+    let next = page.next.map(|c| Cursor { created_at: c.created_at.to_string(), amount: c.amount, id: c.id });
+
+    Ok(serde_json::json!({
+        "entries": page.entries.into_iter().map(entry_json).collect::<Vec<_>>(),
+        "next_cursor": next,
+    }))
 }
 
 #[tauri::command]
@@ -32,7 +130,9 @@ fn add(kind: String, amount: i64, category: String, note: Option<String>) -> Res
     };
     let conn = open_db().map_err(|e| e.to_string())?;
     init_db(&conn).map_err(|e| e.to_string())?;
-    add_entry(&conn, kind, amount, &category, note.as_deref()).map_err(|e| e.to_string())
+    add_entry(&conn, kind, amount, &category, note.as_deref())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -43,13 +143,59 @@ fn delete(id: i64) -> Result<bool, String> {
     Ok(affected > 0)
 }
 
+#[tauri::command]
+fn restore(id: i64) -> Result<bool, String> {
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let affected = restore_entry(&conn, id).map_err(|e| e.to_string())?;
+    Ok(affected > 0)
+}
+
+#[tauri::command]
+fn trash() -> Result<Vec<serde_json::Value>, String> {
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let rows = list_trash(&conn).map_err(|e| e.to_string())?;
+
+    Ok(rows.into_iter().map(|t| {
+        let e = t.entry;
+        serde_json::json!({
+            "id": e.id,
+            "kind": if e.kind == Kind::Expense { "expense" } else { "income" },
+            "amount": e.amount,
+            "category": e.category,
+            "note": e.note,
+            "created_at": e.created_at.to_string(),
+            "deleted_at": t.deleted_at,
+        })
+    })
+    .collect())
+}
+
+#[tauri::command]
+fn undo_last() -> Result<Option<String>, String> {
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let op = undo(&conn).map_err(|e| e.to_string())?;
+    Ok(op.map(|o| o.label))
+}
+
+#[tauri::command]
+fn redo_last() -> Result<Option<String>, String> {
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let op = redo(&conn).map_err(|e| e.to_string())?;
+    Ok(op.map(|o| o.label))
+}
+
 #[tauri::command]
 fn get_month_summary(ym: String) -> Result<serde_json::Value, String> {
+    let ym: YearMonth = ym.parse().map_err(|e: ledger_module::InvalidYearMonth| e.to_string())?;
     let conn = open_db().map_err(|e| e.to_string())?;
     init_db(&conn).map_err(|e| e.to_string())?;
-    let s = month_summary(&conn, &ym).map_err(|e| e.to_string())?;
+    let s = month_summary(&conn, ym).map_err(|e| e.to_string())?;
     Ok(serde_json::json!({
-        "month": s.month,
+        "month": s.month.to_string(),
         "income": s.income,
         "expense": s.expense,
         "balance": s.balance
@@ -63,9 +209,10 @@ fn get_category_totals(ym: String, kind: String) -> Result<Vec<serde_json::Value
         "income" => Kind::Income,
         _ => return Err("kind must be 'expense' or 'income'".into()),
     };
+    let ym: YearMonth = ym.parse().map_err(|e: ledger_module::InvalidYearMonth| e.to_string())?;
     let conn = open_db().map_err(|e| e.to_string())?;
     init_db(&conn).map_err(|e| e.to_string())?;
-    let rows = category_totals_by_kind(&conn, &ym, k).map_err(|e| e.to_string())?;
+    let rows = category_totals_by_kind(&conn, ym, k).map_err(|e| e.to_string())?;
     Ok(rows.into_iter().map(|t| {
         serde_json::json!({
             "category": t.category,
@@ -74,6 +221,198 @@ fn get_category_totals(ym: String, kind: String) -> Result<Vec<serde_json::Value
     }).collect())
 }
 
+#[tauri::command]
+fn get_period_summaries(from: String, to: String, granularity: String) -> Result<Vec<serde_json::Value>, String> {
+    let from = parse_date(&from)?;
+    let to = parse_date(&to)?;
+    let g: Granularity = granularity.parse()?;
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let rows = period_summaries(&conn, from, to, g).map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().map(|p| {
+        serde_json::json!({
+            "label": p.label,
+            "start": p.start.to_string(),
+            "end": p.end.to_string(),
+            "income": p.income,
+            "expense": p.expense,
+            "balance": p.balance,
+        })
+    }).collect())
+}
+
+#[tauri::command]
+fn get_pivot(start: String, end: String, kind: String) -> Result<serde_json::Value, String> {
+    let k = match kind.as_str() {
+        "expense" => Kind::Expense,
+        "income" => Kind::Income,
+        _ => return Err("kind must be 'expense' or 'income'".into()),
+    };
+    let start: YearMonth = start.parse().map_err(|e: ledger_module::InvalidYearMonth| e.to_string())?;
+    let end: YearMonth = end.parse().map_err(|e: ledger_module::InvalidYearMonth| e.to_string())?;
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let t = category_month_pivot(&conn, start, end, k).map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "months": t.months.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
+        "rows": t.rows.iter().map(|r| serde_json::json!({
+            "category": r.category,
+            "values": r.values,
+            "total": r.total,
+            "average": r.average,
+        })).collect::<Vec<_>>(),
+        "month_totals": t.month_totals,
+        "grand_total": t.grand_total,
+        "month_average": t.month_average,
+    }))
+}
+
+fn delta_json(d: &Delta) -> serde_json::Value {
+    serde_json::json!({
+        "current": d.current,
+        "previous": d.previous,
+        "change": d.change,
+        "percent": d.percent,
+    })
+}
+
+#[tauri::command]
+fn get_comparison(
+    current_start: String,
+    current_end: String,
+    previous_start: String,
+    previous_end: String,
+) -> Result<serde_json::Value, String> {
+    let parse = |s: &str| s.parse::<YearMonth>().map_err(|e| e.to_string());
+    let current = (parse(&current_start)?, parse(&current_end)?);
+    let previous = (parse(&previous_start)?, parse(&previous_end)?);
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let c = compare_periods(&conn, current, previous).map_err(|e| e.to_string())?;
+    let cats = |rows: &[ledger_module::CategoryDelta]| {
+        rows.iter().map(|r| serde_json::json!({
+            "category": r.category,
+            "kind": if r.kind == Kind::Expense { "expense" } else { "income" },
+            "delta": delta_json(&r.delta),
+        })).collect::<Vec<_>>()
+    };
+    Ok(serde_json::json!({
+        "income": delta_json(&c.income),
+        "expense": delta_json(&c.expense),
+        "balance": delta_json(&c.balance),
+        "changed": cats(&c.changed),
+        "new": cats(&c.new),
+        "vanished": cats(&c.vanished),
+    }))
+}
+
+#[tauri::command]
+fn get_forecast(months: Option<u32>, history_months: Option<u32>) -> Result<serde_json::Value, String> {
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let today = chrono::Local::now().date_naive();
+    let f = forecast(&conn, today, months.unwrap_or(6), history_months.unwrap_or(3))
+        .map_err(|e| e.to_string())?;
+    let kind = |k: Kind| if k == Kind::Expense { "expense" } else { "income" };
+    Ok(serde_json::json!({
+        "as_of": f.as_of.to_string(),
+        "opening_balance": f.opening_balance,
+        "months": f.months.iter().map(|m| serde_json::json!({
+            "month": m.month.to_string(),
+            "start": m.start.to_string(),
+            "end": m.end.to_string(),
+            "income": m.income,
+            "expense": m.expense,
+            "balance": m.balance,
+        })).collect::<Vec<_>>(),
+        "negative_on": f.negative_on.map(|d| d.to_string()),
+        "averages": f.averages.iter().map(|a| serde_json::json!({
+            "kind": kind(a.kind),
+            "category": a.category,
+            "monthly": a.monthly,
+        })).collect::<Vec<_>>(),
+        "recurring": f.rules.iter().map(|r| serde_json::json!({
+            "id": r.id,
+            "kind": kind(r.kind),
+            "amount": r.amount,
+            "category": r.category,
+            "note": r.note,
+            "day": r.day,
+            "every_months": r.every_months,
+            "start": r.start.to_string(),
+            "end": r.end.map(|m| m.to_string()),
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+#[tauri::command]
+fn get_anomalies(start: String, end: String) -> Result<serde_json::Value, String> {
+    let start: YearMonth = start.parse().map_err(|e: ledger_module::InvalidYearMonth| e.to_string())?;
+    let end: YearMonth = end.parse().map_err(|e: ledger_module::InvalidYearMonth| e.to_string())?;
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let a = find_anomalies(&conn, start, end).map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "outliers": a.outliers.into_iter().map(|o| serde_json::json!({
+            "entry": entry_json(o.entry),
+            "median": o.median,
+            "score": o.score,
+        })).collect::<Vec<_>>(),
+        "spikes": a.spikes.iter().map(|s| serde_json::json!({
+            "category": s.category,
+            "month": s.month.to_string(),
+            "total": s.total,
+            "baseline": s.baseline,
+        })).collect::<Vec<_>>(),
+        "duplicates": a.duplicates.into_iter().map(|d| serde_json::json!({
+            "first": entry_json(d.first),
+            "second": entry_json(d.second),
+            "days_apart": d.days_apart,
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+#[tauri::command]
+fn get_settings() -> Result<serde_json::Value, String> {
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    let s = load_period_settings(&conn).map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "fiscal_year_start_month": s.fiscal_year_start_month,
+        "month_start_day": s.month_start_day,
+    }))
+}
+
+#[tauri::command]
+fn set_settings(fiscal_year_start_month: u32, month_start_day: u32) -> Result<(), String> {
+    if !(1..=12).contains(&fiscal_year_start_month) {
+        return Err("fiscal_year_start_month must be 1-12".into());
+    }
+    if !(1..=31).contains(&month_start_day) {
+        return Err("month_start_day must be 1-31".into());
+    }
+    let conn = open_db().map_err(|e| e.to_string())?;
+    init_db(&conn).map_err(|e| e.to_string())?;
+    save_period_settings(&conn, &PeriodSettings { fiscal_year_start_month, month_start_day })
+        .map_err(|e| e.to_string())
+}
+
+// Whether the database needs a passphrase before any other command can run.
+#[tauri::command]
+fn db_status() -> serde_json::Value {
+    let encrypted = is_encrypted(std::path::Path::new(DB_PATH));
+    serde_json::json!({
+        "encrypted": encrypted,
+        "unlocked": !encrypted || is_unlocked(),
+        "supported": ENCRYPTION_SUPPORTED,
+    })
+}
+
+#[tauri::command]
+fn unlock_db(passphrase: String) -> Result<(), String> {
+    unlock(&passphrase).map_err(|e| e.to_string())
+}
+
 fn main() {
   tauri::Builder::default()
     .setup(|app| {
@@ -92,7 +431,7 @@ fn main() {
         }
       }
     })
-    .invoke_handler(tauri::generate_handler![list, add, delete, get_month_summary, get_category_totals])
+    .invoke_handler(tauri::generate_handler![list, list_page, add, delete, restore, trash, undo_last, redo_last, get_month_summary, get_category_totals, get_period_summaries, get_pivot, get_comparison, get_forecast, get_anomalies, get_settings, set_settings, db_status, unlock_db])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
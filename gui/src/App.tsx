@@ -13,16 +13,23 @@ type Row = {
   note?: string | null;
   created_at: string;
 };
+type Cursor = { created_at: string; amount: number; id: number };
+type Page = { entries: Row[]; next_cursor: Cursor | null };
 type Summary = { month: string; income: number; expense: number; balance: number };
 type CatRow = { category: string; total: number };
+type DbStatus = { encrypted: boolean; unlocked: boolean; supported: boolean };
+
+const PAGE_SIZE = 100;
 
 export default function App() {
   const [rows, setRows] = useState<Row[]>([]);
+  const [nextCursor, setNextCursor] = useState<Cursor | null>(null);
   const [kind, setKind] = useState<"expense" | "income">("expense");
   const [amount, setAmount] = useState("");
   const [category, setCategory] = useState("");
   const [note, setNote] = useState("");
   const [busyId, setBusyId] = useState<number | null>(null);
+  const [lastDeleted, setLastDeleted] = useState<number | null>(null);
   const [ym, setYm] = useState(() => {
   const d = new Date();
   const mm = String(d.getMonth()+1).padStart(2, "0");
@@ -31,10 +38,33 @@ export default function App() {
   const [summary, setSummary] = useState<Summary | null>(null);
   const [kindTab, setKindTab] = useState<"expense"|"income">("expense");
   const [cats, setCats] = useState<CatRow[]>([]);
+  const [dbStatus, setDbStatus] = useState<DbStatus | null>(null);
+  const [passphrase, setPassphrase] = useState("");
+  const [unlockError, setUnlockError] = useState<string | null>(null);
+  const ready = dbStatus?.unlocked === true;
+
+  async function onUnlock() {
+    try {
+      await invoke("unlock_db", { passphrase });
+      setPassphrase("");
+      setUnlockError(null);
+      setDbStatus((s) => (s ? { ...s, unlocked: true } : s));
+    } catch (e) {
+      setUnlockError(String(e));
+    }
+  }
 
   async function refresh() {
-    const res = (await invoke("list")) as Row[];
-    setRows(res);
+    const page = (await invoke("list_page", { pageSize: PAGE_SIZE })) as Page;
+    setRows(page.entries);
+    setNextCursor(page.next_cursor);
+  }
+
+  async function loadMore() {
+    if (!nextCursor) return;
+    const page = (await invoke("list_page", { cursor: nextCursor, pageSize: PAGE_SIZE })) as Page;
+    setRows((prev) => [...prev, ...page.entries]);
+    setNextCursor(page.next_cursor);
   }
 
   async function onAdd() {
@@ -61,12 +91,29 @@ export default function App() {
       setBusyId(id);
       const ok = (await invoke("delete", { id })) as boolean;
       if (!ok) alert("No entry deleted (already removed?)");
+      else setLastDeleted(id);
       await refresh();
     } finally {
       setBusyId(null);
     }
   }
 
+  async function onUndoDelete() {
+    if (lastDeleted === null) return;
+    await invoke("restore", { id: lastDeleted });
+    setLastDeleted(null);
+    await refresh();
+    await loadSummaryAndCats(ym, kindTab);
+  }
+
+  async function onUndoRedo(cmd: "undo_last" | "redo_last") {
+    const label = (await invoke(cmd)) as string | null;
+    if (label === null) alert(cmd === "undo_last" ? "Nothing to undo" : "Nothing to redo");
+    setLastDeleted(null);
+    await refresh();
+    await loadSummaryAndCats(ym, kindTab);
+  }
+
   async function loadSummaryAndCats(y: string, k: "expense"|"income") {
     const s = (await invoke("get_month_summary", { ym: y })) as Summary;
     const c = (await invoke("get_category_totals", { ym: y, kind: k })) as CatRow[];
@@ -74,18 +121,46 @@ export default function App() {
     setCats(c);
   }
 
-  useEffect(() => { // 初回
+  useEffect(() => { // 暗号化されたDBはロック解除が先
+    (invoke("db_status") as Promise<DbStatus>).then(setDbStatus);
+  }, []);
+
+  useEffect(() => { // 初回（ロック解除後）
+    if (!ready) return;
     loadSummaryAndCats(ym, kindTab);
     refresh(); // 既存の一覧ロード
-  }, []);
+  }, [ready]);
 
   useEffect(() => { // 月 or タブが変わったら再ロード
+    if (!ready) return;
     loadSummaryAndCats(ym, kindTab);
   }, [ym, kindTab]);
 
-  useEffect(() => {
-    refresh();
-  }, []);
+  if (dbStatus && !dbStatus.unlocked) {
+    return (
+      <div style={{ fontFamily: "system-ui, sans-serif", padding: 24 }}>
+        <h1>Ledger (Desktop)</h1>
+        {dbStatus.supported ? (
+          <form
+            onSubmit={(e) => { e.preventDefault(); onUnlock(); }}
+            style={{ display: "flex", gap: 8, alignItems: "center" }}
+          >
+            <input
+              type="password"
+              placeholder="passphrase"
+              autoFocus
+              value={passphrase}
+              onChange={(e) => setPassphrase(e.target.value)}
+            />
+            <button type="submit">Unlock</button>
+          </form>
+        ) : (
+          <div>The ledger is encrypted, but this build has no encryption support (rebuild with the sqlcipher feature).</div>
+        )}
+        {unlockError && <div style={{ color: "#c62828", marginTop: 8 }}>{unlockError}</div>}
+      </div>
+    );
+  }
 
   return (
     <div style={{ fontFamily: "system-ui, sans-serif", padding: 24 }}>
@@ -116,8 +191,18 @@ export default function App() {
         />
         <button onClick={onAdd}>Add</button>
         <button onClick={refresh}>Reload</button>
+        <button onClick={() => onUndoRedo("undo_last")}>Undo</button>
+        <button onClick={() => onUndoRedo("redo_last")}>Redo</button>
       </div>
 
+      {lastDeleted !== null && (
+        <div style={{ display: "flex", gap: 8, alignItems: "center", marginBottom: 12, padding: 8, background: "#fff8e1", border: "1px solid #ffe082", borderRadius: 6 }}>
+          <span>Entry #{lastDeleted} moved to trash.</span>
+          <button onClick={onUndoDelete}>Undo</button>
+          <button onClick={() => setLastDeleted(null)}>Dismiss</button>
+        </div>
+      )}
+
       <div style={{ display: "flex", gap: 12, alignItems: "center", marginBottom: 12 }}>
         <input
           type="month"
@@ -216,6 +301,11 @@ export default function App() {
           )}
         </tbody>
       </table>
+      {nextCursor && (
+        <div style={{ textAlign: "center", marginTop: 12 }}>
+          <button onClick={loadMore}>Load more</button>
+        </div>
+      )}
     </div>
   );
 }
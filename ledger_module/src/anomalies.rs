@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rusqlite::Connection;
+
+use crate::settings::load_period_settings;
+use crate::{Entry, EntryFilter, Kind, SortOrder, YearMonth, query_entries};
+
+// Modified z-score above which a single amount is an outlier (Iglewicz & Hoaglin).
+const OUTLIER_SCORE: f64 = 3.5;
+// Fewer amounts than this in a category are not enough to judge.
+const MIN_SAMPLES: usize = 5;
+// A monthly total at least this many times the usual one is a spike.
+const SPIKE_RATIO: f64 = 2.0;
+const SPIKE_HISTORY_MONTHS: i32 = 6;
+const MIN_SPIKE_HISTORY_MONTHS: usize = 3;
+// Same amount and category within this many days may be a double charge.
+const DUPLICATE_DAYS: i64 = 3;
+
+/// An expense far from the usual amount for its category.
+#[derive(Debug)]
+pub struct Outlier {
+    pub entry: Entry,
+    /// Median amount of the category over the whole history.
+    pub median: f64,
+    /// Robust z-score: distance from the median in units of the median absolute deviation.
+    pub score: f64,
+}
+
+/// A month in which a category's expense total is well above its usual level.
+#[derive(Debug)]
+pub struct Spike {
+    pub category: String,
+    pub month: YearMonth,
+    pub total: i64,
+    /// Median monthly total of the preceding months.
+    pub baseline: f64,
+}
+
+/// Two expenses with the same amount and category a few days apart.
+#[derive(Debug)]
+pub struct Duplicate {
+    pub first: Entry,
+    pub second: Entry,
+    pub days_apart: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct Anomalies {
+    /// Largest score first.
+    pub outliers: Vec<Outlier>,
+    /// Largest ratio to the baseline first.
+    pub spikes: Vec<Spike>,
+    /// Oldest first.
+    pub duplicates: Vec<Duplicate>,
+}
+
+/// Looks for unusual expenses in the months `start..=end`, judged against the
+/// whole history of each category.
+pub fn find_anomalies(conn: &Connection, start: YearMonth, end: YearMonth) -> rusqlite::Result<Anomalies> {
+    let settings = load_period_settings(conn)?;
+    let entries = query_entries(conn, &EntryFilter::new().kind(Kind::Expense).order(SortOrder::DateAsc))?;
+    let in_range = |e: &Entry| {
+        let ym = settings.month_of(e.created_at.date());
+        start <= ym && ym <= end
+    };
+
+    let mut amounts: HashMap<&str, Vec<i64>> = HashMap::new();
+    let mut monthly: BTreeMap<(&str, YearMonth), i64> = BTreeMap::new();
+    let mut first_month: HashMap<&str, YearMonth> = HashMap::new();
+    for e in &entries {
+        let ym = settings.month_of(e.created_at.date());
+        amounts.entry(&e.category).or_default().push(e.amount);
+        *monthly.entry((&e.category, ym)).or_default() += e.amount;
+        first_month.entry(&e.category).or_insert(ym);
+    }
+
+    let mut out = Anomalies::default();
+
+    let mut stats: HashMap<&str, Option<(f64, f64)>> = HashMap::new();
+    for e in entries.iter().filter(|e| in_range(e)) {
+        let spread = *stats.entry(&e.category).or_insert_with(|| {
+            let v = &amounts[e.category.as_str()];
+            if v.len() < MIN_SAMPLES { None } else { robust_spread(v) }
+        });
+        let Some((median, spread)) = spread else {
+            continue;
+        };
+        let score = (e.amount as f64 - median) / spread;
+        if score.abs() > OUTLIER_SCORE {
+            out.outliers.push(Outlier {
+                entry: e.clone(),
+                median,
+                score,
+            });
+        }
+    }
+    out.outliers.sort_by(|a, b| b.score.abs().total_cmp(&a.score.abs()));
+
+    for (&(category, ym), &total) in &monthly {
+        if ym < start || ym > end {
+            continue;
+        }
+        let history_start = ym.add_months(-SPIKE_HISTORY_MONTHS).max(first_month[category]);
+        let history: Vec<i64> = history_start
+            .through(ym.pred())
+            .iter()
+            .map(|m| monthly.get(&(category, *m)).copied().unwrap_or(0))
+            .collect();
+        if history.len() < MIN_SPIKE_HISTORY_MONTHS {
+            continue;
+        }
+        let baseline = median(&history);
+        if baseline > 0.0 && total as f64 >= baseline * SPIKE_RATIO {
+            out.spikes.push(Spike {
+                category: category.to_string(),
+                month: ym,
+                total,
+                baseline,
+            });
+        }
+    }
+    out.spikes.sort_by(|a, b| (b.total as f64 / b.baseline).total_cmp(&(a.total as f64 / a.baseline)));
+
+    let recent: Vec<&Entry> = entries.iter().filter(|e| in_range(e)).collect();
+    for (i, a) in recent.iter().enumerate() {
+        for b in &recent[i + 1..] {
+            let days_apart = (b.created_at.date() - a.created_at.date()).num_days();
+            if days_apart > DUPLICATE_DAYS {
+                break;
+            }
+            if a.amount == b.amount && a.category == b.category {
+                out.duplicates.push(Duplicate {
+                    first: (*a).clone(),
+                    second: (*b).clone(),
+                    days_apart,
+                });
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+pub(crate) fn median(values: &[i64]) -> f64 {
+    let mut v = values.to_vec();
+    v.sort_unstable();
+    let n = v.len();
+    if n == 0 {
+        0.0
+    } else if n % 2 == 1 {
+        v[n / 2] as f64
+    } else {
+        (v[n / 2 - 1] + v[n / 2]) as f64 / 2.0
+    }
+}
+
+// Median and the scale that turns a distance from it into a robust z-score:
+// MAD / 0.6745, or 1.2533 × the mean absolute deviation when more than half
+// the amounts are identical. `None` when every amount is the same.
+fn robust_spread(values: &[i64]) -> Option<(f64, f64)> {
+    let m = median(values);
+    let deviations: Vec<f64> = values.iter().map(|&v| (v as f64 - m).abs()).collect();
+    let mut sorted = deviations.clone();
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len();
+    let mad = if n % 2 == 1 { sorted[n / 2] } else { (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0 };
+    if mad > 0.0 {
+        return Some((m, mad / 0.6745));
+    }
+    let mean_ad = deviations.iter().sum::<f64>() / n as f64;
+    if mean_ad > 0.0 { Some((m, mean_ad * 1.2533)) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::{NewEntry, init_db, insert_entry};
+
+    fn spend(conn: &Connection, amount: i64, category: &str, day: &str) {
+        let mut e = NewEntry::new(Kind::Expense, amount, category);
+        e.created_at = NaiveDateTime::parse_from_str(&format!("{} 12:00:00", day), "%Y-%m-%d %H:%M:%S").ok();
+        insert_entry(conn, &e).unwrap();
+    }
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn outliers_spikes_and_duplicates_are_found() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        for (month, coffee) in [("01", 400), ("02", 420), ("03", 380), ("04", 410), ("05", 390)] {
+            spend(&conn, coffee, "coffee", &format!("2025-{}-03", month));
+            spend(&conn, 10000, "food", &format!("2025-{}-05", month));
+        }
+        spend(&conn, 10000, "travel", "2025-05-15");
+        // June: one very expensive coffee, a food month at 2.5× the usual, a
+        // gym fee charged twice, and categories with too little history.
+        spend(&conn, 4000, "coffee", "2025-06-04");
+        spend(&conn, 450, "coffee", "2025-06-18");
+        spend(&conn, 12000, "food", "2025-06-01");
+        spend(&conn, 13000, "food", "2025-06-20");
+        spend(&conn, 5000, "gym", "2025-06-10");
+        spend(&conn, 5000, "gym", "2025-06-12");
+        spend(&conn, 5000, "gym", "2025-06-20");
+        spend(&conn, 100, "books", "2025-06-02");
+        spend(&conn, 10000, "books", "2025-06-25");
+        spend(&conn, 50000, "travel", "2025-06-15");
+
+        let a = find_anomalies(&conn, ym("2025-06"), ym("2025-06")).unwrap();
+
+        let outliers: Vec<(&str, i64)> = a.outliers.iter().map(|o| (o.entry.category.as_str(), o.entry.amount)).collect();
+        assert_eq!(outliers, [("coffee", 4000)]);
+        assert_eq!(a.outliers[0].median, 410.0);
+
+        let spikes: Vec<(&str, i64, f64)> = a.spikes.iter().map(|s| (s.category.as_str(), s.total, s.baseline)).collect();
+        assert_eq!(spikes, [("coffee", 4450, 400.0), ("food", 25000, 10000.0)]);
+
+        let duplicates: Vec<(&str, i64)> =
+            a.duplicates.iter().map(|d| (d.first.category.as_str(), d.days_apart)).collect();
+        assert_eq!(duplicates, [("gym", 2)]);
+
+        // Before June every category was within its usual range.
+        let earlier = find_anomalies(&conn, ym("2025-01"), ym("2025-05")).unwrap();
+        assert!(earlier.outliers.is_empty() && earlier.spikes.is_empty() && earlier.duplicates.is_empty());
+    }
+
+    #[test]
+    fn median_and_spread() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[3, 1, 2]), 2.0);
+        assert_eq!(median(&[4, 1, 3, 2]), 2.5);
+        assert_eq!(robust_spread(&[5, 5, 5]), None);
+        // More than half the amounts equal: the mean absolute deviation is used.
+        let (m, spread) = robust_spread(&[10, 10, 10, 10, 20]).unwrap();
+        assert_eq!(m, 10.0);
+        assert!((spread - 2.0 * 1.2533).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,161 @@
+use rusqlite::{Connection, OptionalExtension, params};
+
+// Columns of `entries` captured in the before/after image of a change.
+const ENTRY_COLUMNS: &[&str] = &[
+    "kind", "amount", "category", "note", "created_at", "deleted_at", "account", "tags",
+];
+
+// SQL expression producing a JSON snapshot of the current `entries` row.
+pub(crate) fn entry_json() -> String {
+    let fields: Vec<String> = ENTRY_COLUMNS.iter().map(|c| format!("'{c}', {c}")).collect();
+    format!("json_object({})", fields.join(", "))
+}
+
+pub(crate) const SCHEMA: &str = r#"
+    CREATE TABLE audit_log (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT,
+        entry_id   INTEGER NOT NULL,
+        action     TEXT NOT NULL,
+        actor      TEXT NOT NULL,
+        changed_at TEXT NOT NULL DEFAULT (datetime('now','localtime')),
+        before     TEXT,
+        after      TEXT
+    );
+    CREATE INDEX audit_log_entry_id ON audit_log(entry_id);
+    CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+    BEGIN
+        SELECT RAISE(ABORT, 'audit_log is append-only');
+    END;
+    CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+    BEGIN
+        SELECT RAISE(ABORT, 'audit_log is append-only');
+    END;
+"#;
+
+/// Name recorded as the actor of every change made through this process.
+/// Taken from `LEDGER_ACTOR`, falling back to the OS user name.
+pub fn current_actor() -> String {
+    ["LEDGER_ACTOR", "USER", "USERNAME"]
+        .iter()
+        .find_map(|k| std::env::var(k).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub(crate) fn snapshot(conn: &Connection, id: i64) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        &format!("SELECT {} FROM entries WHERE id = ?1", entry_json()),
+        params![id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub(crate) fn record(
+    conn: &Connection,
+    entry_id: i64,
+    action: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO audit_log (entry_id, action, actor, before, after, operation_id)
+        VALUES (?1, ?2, ?3, ?4, ?5,
+                (SELECT id FROM operations WHERE state = 'open' ORDER BY id DESC LIMIT 1))
+        "#,
+        params![entry_id, action, current_actor(), before, after],
+    )?;
+    Ok(())
+}
+
+/// Puts row `id` back into the state described by `snapshot`,
+/// deleting it when the snapshot is `None`.
+pub(crate) fn apply_snapshot(conn: &Connection, id: i64, snapshot: Option<&str>) -> rusqlite::Result<()> {
+    let Some(json) = snapshot else {
+        conn.execute("DELETE FROM entries WHERE id = ?1", params![id])?;
+        return Ok(());
+    };
+    let extract: Vec<String> = ENTRY_COLUMNS
+        .iter()
+        .map(|c| format!("json_extract(?2, '$.{c}')"))
+        .collect();
+    let assign: Vec<String> = ENTRY_COLUMNS
+        .iter()
+        .zip(&extract)
+        .map(|(c, e)| format!("{c} = {e}"))
+        .collect();
+    let updated = conn.execute(
+        &format!("UPDATE entries SET {} WHERE id = ?1", assign.join(", ")),
+        params![id, json],
+    )?;
+    if updated == 0 {
+        conn.execute(
+            &format!(
+                "INSERT INTO entries (id, {}) VALUES (?1, {})",
+                ENTRY_COLUMNS.join(", "),
+                extract.join(", ")
+            ),
+            params![id, json],
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub entry_id: i64,
+    pub action: String,
+    pub actor: String,
+    pub changed_at: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+pub fn entry_history(conn: &Connection, entry_id: i64) -> rusqlite::Result<Vec<AuditRecord>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, entry_id, action, actor, changed_at, before, after
+        FROM audit_log
+        WHERE entry_id = ?1
+        ORDER BY id ASC
+        "#,
+    )?;
+    let rows = stmt.query_map(params![entry_id], map_record)?;
+
+    let mut v = Vec::new();
+    for r in rows {
+        v.push(r?);
+    }
+    Ok(v)
+}
+
+pub fn recent_changes(conn: &Connection, limit: i64) -> rusqlite::Result<Vec<AuditRecord>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, entry_id, action, actor, changed_at, before, after
+        FROM audit_log
+        ORDER BY id DESC
+        LIMIT ?1
+        "#,
+    )?;
+    let rows = stmt.query_map(params![limit], map_record)?;
+
+    let mut v = Vec::new();
+    for r in rows {
+        v.push(r?);
+    }
+    Ok(v)
+}
+
+fn map_record(row: &rusqlite::Row) -> rusqlite::Result<AuditRecord> {
+    Ok(AuditRecord {
+        id: row.get(0)?,
+        entry_id: row.get(1)?,
+        action: row.get(2)?,
+        actor: row.get(3)?,
+        changed_at: row.get(4)?,
+        before: row.get(5)?,
+        after: row.get(6)?,
+    })
+}
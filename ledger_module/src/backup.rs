@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags, ffi};
+
+use crate::doctor::{constraint_table, integrity_check};
+use crate::encryption::{apply_key, is_encrypted, match_key};
+use crate::{MIGRATIONS, get_setting, set_setting};
+
+const BACKUP_KEEP: &str = "backup_keep";
+const BACKUP_KEEP_DAYS: &str = "backup_keep_days";
+const AUTO_PREFIX: &str = "ledger-";
+
+// Copy in small steps so other connections (e.g. the GUI) are only blocked briefly.
+const PAGES_PER_STEP: i32 = 64;
+const STEP_PAUSE: Duration = Duration::from_millis(5);
+
+/// How many automatic backups to keep, and for how long.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BackupRetention {
+    /// Newest automatic backups kept; 0 turns automatic backups off.
+    pub keep: u32,
+    /// Older automatic backups are removed after this many days; 0 keeps them
+    /// regardless of age. The newest one is never removed.
+    pub keep_days: u32,
+}
+
+impl Default for BackupRetention {
+    fn default() -> Self {
+        BackupRetention { keep: 10, keep_days: 0 }
+    }
+}
+
+/// Reads the retention settings. Databases from before the settings table
+/// (being migrated right now) get the defaults.
+pub fn load_backup_retention(conn: &Connection) -> BackupRetention {
+    let d = BackupRetention::default();
+    let read = |key: &str, default: u32| {
+        get_setting(conn, key).ok().flatten().and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    BackupRetention {
+        keep: read(BACKUP_KEEP, d.keep),
+        keep_days: read(BACKUP_KEEP_DAYS, d.keep_days),
+    }
+}
+
+pub fn save_backup_retention(conn: &Connection, r: &BackupRetention) -> rusqlite::Result<()> {
+    set_setting(conn, BACKUP_KEEP, &r.keep.to_string())?;
+    set_setting(conn, BACKUP_KEEP_DAYS, &r.keep_days.to_string())
+}
+
+/// Copies the open database to `dest` with SQLite's online backup API, which
+/// is safe while other connections are reading and writing. The copy is
+/// written next to `dest` and only moved into place once it passes
+/// `verify_database`; otherwise the problems are returned and `dest` is left
+/// untouched. Backups of an encrypted database use the same passphrase.
+pub fn backup_database(conn: &Connection, dest: &Path) -> rusqlite::Result<Vec<String>> {
+    let mut tmp = dest.as_os_str().to_owned();
+    tmp.push(".partial");
+    let tmp = PathBuf::from(tmp);
+    let _ = std::fs::remove_file(&tmp);
+    {
+        let mut dst = Connection::open(&tmp)?;
+        match_key(&dst, conn)?;
+        let backup = Backup::new(conn, &mut dst)?;
+        backup.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+    }
+    let problems = verify_database(&tmp)?;
+    if problems.is_empty() {
+        std::fs::rename(&tmp, dest).map_err(|e| io_error(dest, e))?;
+    } else {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    Ok(problems)
+}
+
+/// Checks that `path` is an intact ledger database this version can open:
+/// `PRAGMA integrity_check`, the entries table, and a schema version no newer
+/// than ours. Returns the problems found; empty means it is fine.
+pub fn verify_database(path: &Path) -> rusqlite::Result<Vec<String>> {
+    if !path.is_file() {
+        return Ok(vec![format!("{} does not exist", path.display())]);
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    if let Err(e) = apply_key(&conn, path) {
+        return Ok(vec![e.to_string()]);
+    }
+    // Rows breaking CHECK constraints are copied faithfully; `doctor` reports them.
+    let mut problems = match integrity_check(&conn) {
+        Ok(problems) => problems.into_iter().filter(|p| constraint_table(p).is_none()).collect::<Vec<_>>(),
+        // Not a database at all, or encrypted.
+        Err(e) => return Ok(vec![e.to_string()]),
+    };
+    let has_entries: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'entries')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_entries {
+        problems.push("no entries table (not a ledger database)".to_string());
+    }
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version as usize > MIGRATIONS.len() {
+        problems.push(format!(
+            "schema version {} is newer than this program supports ({})",
+            version,
+            MIGRATIONS.len()
+        ));
+    }
+    Ok(problems)
+}
+
+/// Replaces the contents of the open database with `src`, then applies any
+/// migrations `src` is missing. Check `src` with `verify_database` first.
+/// Both must be plaintext, or encrypted with the current passphrase.
+pub fn restore_database(conn: &mut Connection, src: &Path) -> rusqlite::Result<()> {
+    let target_encrypted = conn.path().is_some_and(|p| is_encrypted(Path::new(p)));
+    if is_encrypted(src) != target_encrypted {
+        return Err(failure(format!(
+            "{} is {}encrypted but the database is {}; use `rekey` to match them first",
+            src.display(),
+            if target_encrypted { "not " } else { "" },
+            if target_encrypted { "encrypted" } else { "not" }
+        )));
+    }
+    {
+        let from = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        apply_key(&from, src)?;
+        let backup = Backup::new(&from, conn)?;
+        backup.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+    }
+    crate::init_db(conn)
+}
+
+/// Directory holding the automatic backups: `backups/` next to the database
+/// file. `None` for in-memory databases.
+pub fn backup_dir(conn: &Connection) -> Option<PathBuf> {
+    let path = conn.path().filter(|p| !p.is_empty())?;
+    Some(Path::new(path).parent().unwrap_or(Path::new(".")).join("backups"))
+}
+
+/// Automatic backups, newest first.
+pub fn list_backups(conn: &Connection) -> Vec<PathBuf> {
+    let Some(dir) = backup_dir(conn) else {
+        return Vec::new();
+    };
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut v: Vec<PathBuf> = read
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(AUTO_PREFIX) && n.ends_with(".db"))
+        })
+        .collect();
+    // Names start with the timestamp, so they sort by age.
+    v.sort();
+    v.reverse();
+    v
+}
+
+/// Writes a timestamped backup to `backup_dir` (e.g. before a migration or
+/// an import) and removes old ones per `BackupRetention`. Returns the new
+/// file, or `None` when automatic backups are off or the database is in
+/// memory.
+pub fn auto_backup(conn: &Connection, reason: &str) -> rusqlite::Result<Option<PathBuf>> {
+    let retention = load_backup_retention(conn);
+    let Some(dir) = backup_dir(conn).filter(|_| retention.keep > 0) else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+    let now = chrono::Local::now();
+    let reason: String = reason
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let dest = dir.join(format!("{}{}-{}.db", AUTO_PREFIX, now.format("%Y%m%d-%H%M%S%3f"), reason));
+    let problems = backup_database(conn, &dest)?;
+    if !problems.is_empty() {
+        return Err(failure(format!("backup failed verification: {}", problems.join("; "))));
+    }
+
+    let cutoff = now - chrono::Duration::days(retention.keep_days as i64);
+    for (i, old) in list_backups(conn).iter().enumerate().skip(1) {
+        let expired = retention.keep_days > 0
+            && std::fs::metadata(old)
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| chrono::DateTime::<chrono::Local>::from(t) < cutoff);
+        if i >= retention.keep as usize || expired {
+            let _ = std::fs::remove_file(old);
+        }
+    }
+    Ok(Some(dest))
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> rusqlite::Error {
+    failure(format!("{}: {}", path.display(), e))
+}
+
+pub(crate) fn failure(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_ERROR), Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntryFilter, Kind, add_entry, init_db, query_entries};
+
+    // An empty directory of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ledger-backup-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn open(path: &Path) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        init_db(&conn).unwrap();
+        conn
+    }
+
+    fn entries(conn: &Connection) -> String {
+        format!("{:?}", query_entries(conn, &EntryFilter::new()).unwrap())
+    }
+
+    #[test]
+    fn backup_verifies_and_restores_the_same_entries() {
+        let dir = scratch("restore");
+        let conn = open(&dir.join("ledger.db"));
+        add_entry(&conn, Kind::Expense, 1200, "food", Some("lunch")).unwrap();
+        add_entry(&conn, Kind::Income, 300000, "salary", None).unwrap();
+
+        let dest = dir.join("copy.db");
+        assert!(backup_database(&conn, &dest).unwrap().is_empty());
+        assert!(verify_database(&dest).unwrap().is_empty());
+        assert!(!dir.join("copy.db.partial").exists());
+
+        let mut other = open(&dir.join("other.db"));
+        add_entry(&other, Kind::Expense, 5, "replaced", None).unwrap();
+        restore_database(&mut other, &dest).unwrap();
+        assert_eq!(entries(&other), entries(&conn));
+        drop((conn, other));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_that_are_not_ledgers_fail_verification() {
+        let dir = scratch("verify");
+        let missing = dir.join("missing.db");
+        assert_eq!(verify_database(&missing).unwrap(), [format!("{} does not exist", missing.display())]);
+
+        let garbage = dir.join("garbage.db");
+        std::fs::write(&garbage, [7u8; 4096]).unwrap();
+        assert!(!verify_database(&garbage).unwrap().is_empty());
+
+        let other = dir.join("other.db");
+        Connection::open(&other).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        assert_eq!(verify_database(&other).unwrap(), ["no entries table (not a ledger database)"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retention_removes_only_the_oldest_automatic_backups() {
+        let dir = scratch("retention");
+        let conn = open(&dir.join("ledger.db"));
+        save_backup_retention(&conn, &BackupRetention { keep: 3, keep_days: 0 }).unwrap();
+        let backups = backup_dir(&conn).unwrap();
+        std::fs::create_dir_all(&backups).unwrap();
+        std::fs::write(backups.join("manual.db"), "kept").unwrap();
+
+        let mut made = Vec::new();
+        for i in 0..5 {
+            // Names carry millisecond timestamps.
+            std::thread::sleep(Duration::from_millis(2));
+            made.push(auto_backup(&conn, &format!("step {}", i)).unwrap().unwrap());
+        }
+        made.reverse();
+        assert_eq!(list_backups(&conn), made[..3]);
+        assert!(made[3..].iter().all(|p| !p.exists()));
+        assert!(backups.join("manual.db").exists());
+        assert!(made[0].to_string_lossy().ends_with("-step_4.db"));
+
+        // Off: nothing written, nothing removed.
+        save_backup_retention(&conn, &BackupRetention { keep: 0, keep_days: 0 }).unwrap();
+        assert_eq!(auto_backup(&conn, "off").unwrap(), None);
+        assert_eq!(list_backups(&conn).len(), 3);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use rusqlite::Connection;
+
+use crate::{Kind, YearMonth, category_totals_by_kind_in_range, summary_in_range};
+
+/// A value in the current period next to the same value in the period it is compared with.
+#[derive(Copy, Clone, Debug)]
+pub struct Delta {
+    pub current: i64,
+    pub previous: i64,
+    pub change: i64,
+    /// Change relative to the size of the previous value; `None` when it is zero.
+    pub percent: Option<f64>,
+}
+
+impl Delta {
+    pub fn new(current: i64, previous: i64) -> Self {
+        Delta {
+            current,
+            previous,
+            change: current - previous,
+            percent: if previous == 0 {
+                None
+            } else {
+                Some((current - previous) as f64 * 100.0 / previous.abs() as f64)
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CategoryDelta {
+    pub category: String,
+    pub kind: Kind,
+    pub delta: Delta,
+}
+
+#[derive(Debug)]
+pub struct PeriodComparison {
+    pub current: (YearMonth, YearMonth),
+    pub previous: (YearMonth, YearMonth),
+    pub income: Delta,
+    pub expense: Delta,
+    pub balance: Delta,
+    /// Categories present in both periods, largest absolute change first.
+    pub changed: Vec<CategoryDelta>,
+    /// Categories only in the current period.
+    pub new: Vec<CategoryDelta>,
+    /// Categories only in the previous period.
+    pub vanished: Vec<CategoryDelta>,
+}
+
+/// Compares two month ranges, e.g. a month with the month before or with the
+/// same month a year earlier.
+pub fn compare_periods(
+    conn: &Connection,
+    current: (YearMonth, YearMonth),
+    previous: (YearMonth, YearMonth),
+) -> rusqlite::Result<PeriodComparison> {
+    let cur = summary_in_range(conn, current.0, current.1)?;
+    let prev = summary_in_range(conn, previous.0, previous.1)?;
+
+    let mut changed = Vec::new();
+    let mut new = Vec::new();
+    let mut vanished = Vec::new();
+    for kind in [Kind::Expense, Kind::Income] {
+        let mut totals: BTreeMap<String, (Option<i64>, Option<i64>)> = BTreeMap::new();
+        for t in category_totals_by_kind_in_range(conn, current.0, current.1, kind)? {
+            totals.entry(t.category).or_default().0 = Some(t.total);
+        }
+        for t in category_totals_by_kind_in_range(conn, previous.0, previous.1, kind)? {
+            totals.entry(t.category).or_default().1 = Some(t.total);
+        }
+        for (category, (c, p)) in totals {
+            let d = CategoryDelta {
+                category,
+                kind,
+                delta: Delta::new(c.unwrap_or(0), p.unwrap_or(0)),
+            };
+            match (c, p) {
+                (Some(_), Some(_)) => changed.push(d),
+                (Some(_), None) => new.push(d),
+                _ => vanished.push(d),
+            }
+        }
+    }
+    changed.sort_by_key(|d| Reverse(d.delta.change.abs()));
+    new.sort_by_key(|d| Reverse(d.delta.current));
+    vanished.sort_by_key(|d| Reverse(d.delta.previous));
+
+    Ok(PeriodComparison {
+        current,
+        previous,
+        income: Delta::new(cur.income, prev.income),
+        expense: Delta::new(cur.expense, prev.expense),
+        balance: Delta::new(cur.balance, prev.balance),
+        changed,
+        new,
+        vanished,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::{NewEntry, init_db, insert_entry};
+
+    fn add(conn: &Connection, kind: Kind, amount: i64, category: &str, day: &str) {
+        let mut e = NewEntry::new(kind, amount, category);
+        e.created_at = NaiveDateTime::parse_from_str(&format!("{} 12:00:00", day), "%Y-%m-%d %H:%M:%S").ok();
+        insert_entry(conn, &e).unwrap();
+    }
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    fn month(s: &str) -> (YearMonth, YearMonth) {
+        (ym(s), ym(s))
+    }
+
+    fn categories(v: &[CategoryDelta]) -> Vec<(&str, i64, i64)> {
+        v.iter().map(|d| (d.category.as_str(), d.delta.current, d.delta.previous)).collect()
+    }
+
+    fn ledger() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        add(&conn, Kind::Income, 300000, "salary", "2025-05-25");
+        add(&conn, Kind::Expense, 40000, "food", "2025-05-10");
+        add(&conn, Kind::Expense, 10000, "gym", "2025-05-01");
+        add(&conn, Kind::Expense, 55000, "food", "2025-06-10");
+        add(&conn, Kind::Expense, 5000, "books", "2025-06-15");
+        conn
+    }
+
+    #[test]
+    fn month_over_month_deltas() {
+        let conn = ledger();
+        let c = compare_periods(&conn, month("2025-06"), month("2025-05")).unwrap();
+
+        assert_eq!((c.income.change, c.income.percent), (-300000, Some(-100.0)));
+        assert_eq!((c.expense.change, c.expense.percent), (10000, Some(20.0)));
+        assert_eq!((c.balance.current, c.balance.previous), (-60000, 250000));
+        assert_eq!(c.balance.percent, Some(-124.0));
+
+        assert_eq!(categories(&c.changed), [("food", 55000, 40000)]);
+        assert_eq!(categories(&c.new), [("books", 5000, 0)]);
+        assert_eq!(categories(&c.vanished), [("salary", 0, 300000), ("gym", 0, 10000)]);
+    }
+
+    #[test]
+    fn an_empty_previous_period_has_no_percentages() {
+        let conn = ledger();
+        let c = compare_periods(&conn, month("2025-05"), month("2025-04")).unwrap();
+
+        assert_eq!((c.income.change, c.income.percent), (300000, None));
+        assert_eq!((c.expense.change, c.expense.percent), (50000, None));
+        assert!(c.changed.is_empty() && c.vanished.is_empty());
+        assert_eq!(categories(&c.new), [("salary", 300000, 0), ("food", 40000, 0), ("gym", 10000, 0)]);
+        assert!(c.new.iter().all(|d| d.delta.percent.is_none()));
+
+        // Both periods empty.
+        let c = compare_periods(&conn, month("2025-03"), month("2025-02")).unwrap();
+        assert_eq!((c.balance.change, c.balance.percent), (0, None));
+        assert!(c.changed.is_empty() && c.new.is_empty() && c.vanished.is_empty());
+    }
+
+    #[test]
+    fn percentages_are_relative_to_the_size_of_the_previous_value() {
+        // A smaller deficit is an improvement.
+        assert_eq!(Delta::new(-50, -100).percent, Some(50.0));
+        assert_eq!(Delta::new(-150, -100).percent, Some(-50.0));
+        assert_eq!(Delta::new(100, 0).percent, None);
+    }
+}
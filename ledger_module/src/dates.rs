@@ -0,0 +1,199 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+/// A calendar month, e.g. `2025-08`. Parsing and `new` only accept years that
+/// fit the `YYYY-MM` form; arithmetic stops at the first and last months chrono
+/// can represent, so a `YearMonth` can always be turned into a date range.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct YearMonth {
+    year: i32,
+    month: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct InvalidYearMonth(pub String);
+
+impl fmt::Display for InvalidYearMonth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid month: {} (expected YYYY-MM)", self.0)
+    }
+}
+
+impl std::error::Error for InvalidYearMonth {}
+
+/// Years `YearMonth::new` accepts: those written with four digits.
+pub const YEARS: std::ops::RangeInclusive<i32> = 0..=9999;
+
+impl YearMonth {
+    pub fn new(year: i32, month: u32) -> Option<Self> {
+        (YEARS.contains(&year) && (1..=12).contains(&month)).then_some(YearMonth { year, month })
+    }
+
+    pub fn of(date: NaiveDate) -> Self {
+        YearMonth {
+            year: date.year(),
+            month: date.month(),
+        }
+    }
+
+    pub fn year(self) -> i32 {
+        self.year
+    }
+
+    pub fn month(self) -> u32 {
+        self.month
+    }
+
+    pub fn first_day(self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year, self.month, 1).expect("validated on construction")
+    }
+
+    pub fn last_day(self) -> NaiveDate {
+        let next = if self.month == 12 {
+            NaiveDate::from_ymd_opt(self.year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(self.year, self.month + 1, 1)
+        };
+        match next {
+            Some(d) => d.pred_opt().expect("not the first representable day"),
+            // December of chrono's last year.
+            None => NaiveDate::from_ymd_opt(self.year, 12, 31).expect("validated on construction"),
+        }
+    }
+
+    /// The next month; the last representable month stays where it is.
+    pub fn succ(self) -> Self {
+        self.add_months(1)
+    }
+
+    /// The previous month; the first representable month stays where it is.
+    pub fn pred(self) -> Self {
+        self.add_months(-1)
+    }
+
+    /// `n` months later (earlier when negative), clamped to the months chrono
+    /// can represent.
+    pub fn add_months(self, n: i32) -> Self {
+        let index = |ym: YearMonth| ym.year as i64 * 12 + ym.month as i64 - 1;
+        let first = YearMonth::of(NaiveDate::MIN);
+        let last = YearMonth { year: NaiveDate::MAX.year(), month: 12 };
+        let i = (index(self) + n as i64).clamp(index(first), index(last));
+        YearMonth {
+            year: i.div_euclid(12) as i32,
+            month: i.rem_euclid(12) as u32 + 1,
+        }
+    }
+
+    /// Months from `earlier` to `self`; negative when `earlier` comes after `self`.
+    pub fn months_since(self, earlier: YearMonth) -> i32 {
+        (self.year - earlier.year) * 12 + self.month as i32 - earlier.month as i32
+    }
+
+    /// Every month from `self` to `end`, both inclusive.
+    pub fn through(self, end: YearMonth) -> Vec<YearMonth> {
+        let mut v = Vec::new();
+        let mut m = self;
+        while m <= end {
+            v.push(m);
+            if m == end {
+                break;
+            }
+            m = m.succ();
+        }
+        v
+    }
+}
+
+impl fmt::Display for YearMonth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+impl FromStr for YearMonth {
+    type Err = InvalidYearMonth;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || InvalidYearMonth(s.to_string());
+        let (y, m) = s.split_once('-').ok_or_else(err)?;
+        if y.len() != 4 || m.len() != 2 || !y.bytes().chain(m.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        let year = y.parse().map_err(|_| err())?;
+        let month = m.parse().map_err(|_| err())?;
+        YearMonth::new(year, month).ok_or_else(err)
+    }
+}
+
+// Stored as `YYYY-MM` text.
+impl ToSql for YearMonth {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl FromSql for YearMonth {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_str()?.parse().map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+// `created_at` is stored as `YYYY-MM-DD HH:MM:SS` text, which sorts the same
+// as the instant it names. Range queries compare against these bounds directly
+// so the `entries(created_at)` index can be used.
+pub(crate) fn day_start(d: NaiveDate) -> String {
+    format!("{} 00:00:00", d.format("%Y-%m-%d"))
+}
+
+pub(crate) fn format_timestamp(t: NaiveDateTime) -> String {
+    t.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn construction_rejects_years_outside_four_digits() {
+        assert_eq!(YearMonth::new(0, 1), Some(ym("0000-01")));
+        assert_eq!(YearMonth::new(9999, 12), Some(ym("9999-12")));
+        assert_eq!(YearMonth::new(10000, 1), None);
+        assert_eq!(YearMonth::new(-1, 12), None);
+        assert_eq!(YearMonth::new(262142, 1), None);
+        assert_eq!(YearMonth::new(2025, 13), None);
+        assert!("2025-00".parse::<YearMonth>().is_err());
+        assert!("12025-01".parse::<YearMonth>().is_err());
+    }
+
+    #[test]
+    fn arithmetic_stops_at_the_representable_months() {
+        assert_eq!(ym("9999-12").succ(), YearMonth::of(NaiveDate::from_ymd_opt(10000, 1, 1).unwrap()));
+        assert_eq!(ym("0000-01").pred(), YearMonth::of(NaiveDate::from_ymd_opt(-1, 12, 1).unwrap()));
+        assert_eq!(ym("2025-11").add_months(14), ym("2027-01"));
+        assert_eq!(ym("2025-01").add_months(-13), ym("2023-12"));
+
+        let last = ym("2025-01").add_months(i32::MAX);
+        let first = ym("2025-01").add_months(i32::MIN);
+        assert_eq!(last.succ(), last);
+        assert_eq!(first.pred(), first);
+        assert_eq!(last.last_day(), NaiveDate::MAX);
+        assert_eq!(first.first_day(), NaiveDate::MIN);
+        assert_eq!(last.first_day().month(), 12);
+        assert_eq!(last.add_months(-1).through(last).len(), 2);
+        assert_eq!(first.through(first.succ()), [first, first.succ()]);
+    }
+
+    #[test]
+    fn last_day_follows_the_calendar() {
+        assert_eq!(ym("2024-02").last_day(), NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+        assert_eq!(ym("2025-02").last_day(), NaiveDate::from_ymd_opt(2025, 2, 28).unwrap());
+        assert_eq!(ym("2025-12").last_day(), NaiveDate::from_ymd_opt(2025, 12, 31).unwrap());
+    }
+}
@@ -0,0 +1,391 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use rusqlite::types::Value;
+use rusqlite::{Connection, params};
+
+use crate::dates::{self, YearMonth};
+use crate::filter::{join_tags, split_tags};
+use crate::{MIGRATIONS, audit, run_operation};
+
+/// Something wrong with the database, found by `diagnose`.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// Where it is: `entry #12`, `recurring #3`, `import ref zaim/…`, ...
+    pub subject: String,
+    pub message: String,
+    fix: Option<Fix>,
+}
+
+impl Problem {
+    /// Whether `repair` can fix this without guessing at the user's data.
+    pub fn fixable(&self) -> bool {
+        self.fix.is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Fix {
+    // Rewrite one column of an entry (audited, undoable).
+    EntryColumn { id: i64, column: &'static str, value: Option<String> },
+    // Reference to an entry that is gone for good.
+    ImportRef { source: String, reference: String },
+    // An operation that never finished; later audit records would attach to it.
+    OpenOperation(i64),
+}
+
+/// Runs SQLite's `integrity_check` and `foreign_key_check`, then checks the
+/// ledger's own rules: kinds, positive whole amounts, timestamps the app can
+/// read, tags, recurring rules, import references and the undo journal. When
+/// the file itself is damaged only `integrity_check`'s findings are returned.
+pub fn diagnose(conn: &Connection) -> rusqlite::Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    let mut damaged = false;
+    for message in integrity_check(conn)? {
+        match constraint_table(&message) {
+            // Reported row by row below.
+            Some("entries") => {}
+            Some(table) => problems.push(problem(format!("table {}", table), message, None)),
+            None => {
+                damaged = true;
+                problems.push(problem("database", message, None));
+            }
+        }
+    }
+    if damaged {
+        return Ok(problems);
+    }
+    problems.extend(foreign_key_problems(conn)?);
+    // After a failed migration only the checks whose tables and columns exist
+    // can run.
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if (version as usize) < MIGRATIONS.len() {
+        problems.push(problem(
+            "schema",
+            format!("schema incomplete (version {} of {})", version, MIGRATIONS.len()),
+            None,
+        ));
+    }
+    if has_columns(conn, "entries", &["deleted_at", "account", "tags"])? {
+        problems.extend(entry_problems(conn)?);
+    }
+    if has_columns(conn, "recurring", &["start_month", "end_month"])? {
+        problems.extend(recurring_problems(conn)?);
+    }
+    if has_columns(conn, "import_refs", &["source", "ref", "entry_id"])?
+        && has_columns(conn, "audit_log", &["entry_id", "operation_id"])?
+        && has_columns(conn, "operations", &["state"])?
+    {
+        problems.extend(import_ref_problems(conn)?);
+    }
+    if has_columns(conn, "operations", &["label", "state"])? {
+        problems.extend(journal_problems(conn)?);
+    }
+    Ok(problems)
+}
+
+/// Applies the fixes of every fixable problem `diagnose` finds. Entry changes
+/// form one undoable operation. Returns how many problems were fixed.
+pub fn repair(conn: &Connection) -> rusqlite::Result<usize> {
+    let fixes: Vec<Fix> = diagnose(conn)?.into_iter().filter_map(|p| p.fix).collect();
+    if fixes.is_empty() {
+        return Ok(0);
+    }
+    run_operation(conn, "doctor --fix", |tx| {
+        for fix in &fixes {
+            match fix {
+                Fix::EntryColumn { id, column, value } => {
+                    let before = audit::snapshot(tx, *id)?;
+                    tx.execute(&format!("UPDATE entries SET {} = ?2 WHERE id = ?1", column), params![id, value])?;
+                    let after = audit::snapshot(tx, *id)?;
+                    audit::record(tx, *id, "repair", before.as_deref(), after.as_deref())?;
+                }
+                Fix::ImportRef { source, reference } => {
+                    tx.execute(
+                        "DELETE FROM import_refs WHERE source = ?1 AND ref = ?2",
+                        params![source, reference],
+                    )?;
+                }
+                Fix::OpenOperation(id) => {
+                    tx.execute("UPDATE operations SET state = 'discarded' WHERE id = ?1", params![id])?;
+                }
+            }
+        }
+        Ok(fixes.len())
+    })
+}
+
+/// Rebuilds the file to reclaim free pages (`VACUUM`) and refreshes the query
+/// planner's statistics (`ANALYZE`).
+pub fn vacuum(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("VACUUM; ANALYZE; PRAGMA optimize;")
+}
+
+/// The table named by an `integrity_check` message about a CHECK or NOT NULL
+/// constraint. Such rows break the ledger's rules, not the file.
+pub(crate) fn constraint_table(message: &str) -> Option<&str> {
+    message
+        .strip_prefix("CHECK constraint failed in ")
+        .or_else(|| message.strip_prefix("NULL value in ").map(|rest| rest.split('.').next().unwrap_or(rest)))
+}
+
+/// `PRAGMA integrity_check`; empty when the database is intact.
+pub(crate) fn integrity_check(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut out = Vec::new();
+    for r in rows {
+        let r = r?;
+        if r != "ok" {
+            out.push(r);
+        }
+    }
+    Ok(out)
+}
+
+// Whether `table` exists with all of `columns`.
+fn has_columns(conn: &Connection, table: &str, columns: &[&str]) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let present = stmt.query_map([table], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(!present.is_empty() && columns.iter().all(|c| present.iter().any(|p| p == c)))
+}
+
+fn problem(subject: impl Into<String>, message: String, fix: Option<Fix>) -> Problem {
+    Problem { subject: subject.into(), message, fix }
+}
+
+fn foreign_key_problems(conn: &Connection) -> rusqlite::Result<Vec<Problem>> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, String>(2)?))
+    })?;
+    let mut out = Vec::new();
+    for r in rows {
+        let (table, rowid, parent) = r?;
+        let subject = match rowid {
+            Some(id) => format!("{} row {}", table, id),
+            None => table,
+        };
+        out.push(problem(subject, format!("refers to a row missing from {}", parent), None));
+    }
+    Ok(out)
+}
+
+fn entry_problems(conn: &Connection) -> rusqlite::Result<Vec<Problem>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, kind, typeof(amount), CAST(amount AS TEXT), category,
+               CAST(created_at AS TEXT), CAST(deleted_at AS TEXT), tags
+        FROM entries ORDER BY id
+        "#,
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Value>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, Option<String>>(7)?,
+        ))
+    })?;
+    let mut out = Vec::new();
+    for r in rows {
+        let (id, kind, amount_type, amount, category, created_at, deleted_at, tags) = r?;
+        let subject = format!("entry #{}", id);
+        let mut push = |message: String, fix: Option<Fix>| out.push(problem(subject.clone(), message, fix));
+
+        match kind {
+            Value::Integer(0 | 1) => {}
+            Value::Integer(k) => push(format!("kind {} is neither expense (0) nor income (1)", k), None),
+            Value::Null => push("has no kind".to_string(), None),
+            other => push(format!("kind {:?} is neither expense (0) nor income (1)", other), None),
+        }
+        let amount = amount.unwrap_or_default();
+        if amount_type != "integer" {
+            push(format!("amount '{}' is not a whole number", amount), None);
+        } else if amount.parse::<i64>().is_ok_and(|a| a <= 0) {
+            push(format!("amount {} is not positive", amount), None);
+        }
+        if category.as_deref().is_none_or(|c| c.trim().is_empty()) {
+            push("has no category".to_string(), None);
+        }
+        for (column, value) in [("created_at", created_at.as_deref()), ("deleted_at", deleted_at.as_deref())] {
+            let Some(value) = value else {
+                if column == "created_at" {
+                    push("has no created_at".to_string(), None);
+                }
+                continue;
+            };
+            if NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .is_ok_and(|t| dates::format_timestamp(t) == value)
+            {
+                continue;
+            }
+            match parse_lenient(value) {
+                Some(t) => {
+                    let fixed = dates::format_timestamp(t);
+                    push(
+                        format!("{} '{}' is not in YYYY-MM-DD HH:MM:SS form (would become '{}')", column, value, fixed),
+                        Some(Fix::EntryColumn { id, column, value: Some(fixed) }),
+                    );
+                }
+                None => push(format!("{} '{}' is not a date", column, value), None),
+            }
+        }
+        let normalized = join_tags(&split_tags(tags.as_deref()));
+        if tags != normalized {
+            push(
+                format!("tags {:?} have empty or padded items", tags.as_deref().unwrap_or("")),
+                Some(Fix::EntryColumn { id, column: "tags", value: normalized }),
+            );
+        }
+    }
+    Ok(out)
+}
+
+// Timestamps written by hand or by other tools: ISO 8601 with `T`, fractional
+// seconds, a UTC offset (kept as local wall time), or a date alone.
+fn parse_lenient(s: &str) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.naive_local());
+    }
+    for f in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y/%m/%d %H:%M:%S"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, f) {
+            return Some(t);
+        }
+    }
+    ["%Y-%m-%d", "%Y/%m/%d"]
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(s, f).ok())
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+}
+
+fn recurring_problems(conn: &Connection) -> rusqlite::Result<Vec<Problem>> {
+    let mut stmt = conn.prepare("SELECT id, start_month, end_month FROM recurring ORDER BY id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+    })?;
+    let mut out = Vec::new();
+    for r in rows {
+        let (id, start, end) = r?;
+        let subject = format!("recurring #{}", id);
+        let start_ym = start.parse::<YearMonth>();
+        if start_ym.is_err() {
+            out.push(problem(&subject, format!("start month '{}' is not YYYY-MM", start), None));
+        }
+        let Some(end) = end else {
+            continue;
+        };
+        match (start_ym, end.parse::<YearMonth>()) {
+            (_, Err(_)) => out.push(problem(&subject, format!("end month '{}' is not YYYY-MM", end), None)),
+            (Ok(s), Ok(e)) if e < s => {
+                out.push(problem(&subject, format!("ends ({}) before it starts ({})", end, start), None))
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+// References whose entry no longer exists and cannot come back through redo.
+// Harmless to imports (they join against entries), so removing them is safe.
+fn import_ref_problems(conn: &Connection) -> rusqlite::Result<Vec<Problem>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT r.source, r.ref, r.entry_id FROM import_refs r
+        WHERE NOT EXISTS (SELECT 1 FROM entries e WHERE e.id = r.entry_id)
+          AND NOT EXISTS (
+              SELECT 1 FROM audit_log a JOIN operations o ON o.id = a.operation_id
+              WHERE a.entry_id = r.entry_id AND o.state = 'undone'
+          )
+        ORDER BY r.source, r.ref
+        "#,
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+    })?;
+    let mut out = Vec::new();
+    for r in rows {
+        let (source, reference, entry_id) = r?;
+        out.push(problem(
+            format!("import ref {}/{}", source, reference),
+            format!("points to entry #{}, which no longer exists", entry_id),
+            Some(Fix::ImportRef { source, reference }),
+        ));
+    }
+    Ok(out)
+}
+
+fn journal_problems(conn: &Connection) -> rusqlite::Result<Vec<Problem>> {
+    let mut stmt = conn.prepare("SELECT id, label FROM operations WHERE state = 'open' ORDER BY id")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+    let mut out = Vec::new();
+    for r in rows {
+        let (id, label) = r?;
+        out.push(problem(
+            format!("operation #{}", id),
+            format!("'{}' never finished; it cannot be undone", label),
+            Some(Fix::OpenOperation(id)),
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Kind, init_db};
+
+    #[test]
+    fn failed_migration_is_reported_not_fatal() {
+        let conn = Connection::open_in_memory().unwrap();
+        // An `account` column that migration 4 tries to add again.
+        conn.execute_batch(
+            r#"
+            CREATE TABLE entries (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind       INTEGER NOT NULL CHECK(kind IN (0, 1)),
+                amount     INTEGER NOT NULL CHECK(amount > 0),
+                category   TEXT NOT NULL,
+                note       TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now','localtime')),
+                account    TEXT
+            );
+            INSERT INTO entries (kind, amount, category) VALUES (0, 100, 'food');
+            "#,
+        )
+        .unwrap();
+        assert!(init_db(&conn).is_err());
+        // The failed step was rolled back and left no transaction open.
+        assert!(conn.is_autocommit());
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, 3);
+
+        let problems = diagnose(&conn).unwrap();
+        let messages: Vec<String> = problems.iter().map(|p| format!("{}: {}", p.subject, p.message)).collect();
+        assert_eq!(messages, [format!("schema: schema incomplete (version 3 of {})", MIGRATIONS.len())]);
+    }
+
+    #[test]
+    fn fixable_problems_are_repaired() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        crate::add_entry(&conn, Kind::Expense, 100, "food", None).unwrap();
+        conn.execute_batch(
+            "UPDATE entries SET created_at = '2025-09-01T08:30:00', tags = ' a,,b '; \
+             INSERT INTO operations (label, actor) VALUES ('import', 'cli');",
+        )
+        .unwrap();
+        let problems = diagnose(&conn).unwrap();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems.iter().all(Problem::fixable));
+
+        assert_eq!(repair(&conn).unwrap(), 3);
+        assert!(diagnose(&conn).unwrap().is_empty());
+        let (created_at, tags): (String, String) =
+            conn.query_row("SELECT created_at, tags FROM entries", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!((created_at.as_str(), tags.as_str()), ("2025-09-01 08:30:00", "a,b"));
+    }
+}
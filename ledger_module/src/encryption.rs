@@ -0,0 +1,215 @@
+// Optional encryption at rest with SQLCipher (the `sqlcipher` cargo feature).
+// The passphrase is remembered for the life of the process, so every
+// `open_db` (the GUI opens one connection per command) can apply it.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rusqlite::{Connection, ErrorCode, params};
+
+use crate::DB_PATH;
+use crate::backup::{failure, verify_database};
+
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether this build can open and create encrypted databases.
+pub const ENCRYPTION_SUPPORTED: bool = cfg!(feature = "sqlcipher");
+
+/// True when `path` holds data that is not a plain SQLite file, i.e. it
+/// needs a passphrase. Missing and empty files are not encrypted.
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header != PLAINTEXT_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// Checks `passphrase` against the ledger database and remembers it for
+/// later `open_db` calls.
+pub fn unlock(passphrase: &str) -> rusqlite::Result<()> {
+    require_support()?;
+    let conn = Connection::open(DB_PATH)?;
+    key(&conn, passphrase)?;
+    remember(Some(passphrase));
+    Ok(())
+}
+
+/// Whether a passphrase has been accepted by `unlock` (or set by
+/// `change_passphrase`) in this process.
+pub fn is_unlocked() -> bool {
+    PASSPHRASE.lock().unwrap().is_some()
+}
+
+/// Encrypts a plaintext database, changes the passphrase of an encrypted one,
+/// or with `None` decrypts it. Encrypting and decrypting write a new file
+/// with `sqlcipher_export`, verify it and only then replace the database, so
+/// the connection is consumed and a new one returned. Existing backups are
+/// left as they were.
+pub fn change_passphrase(conn: Connection, new: Option<&str>) -> rusqlite::Result<Connection> {
+    require_support()?;
+    let path = match conn.path().filter(|p| !p.is_empty()) {
+        Some(p) => PathBuf::from(p),
+        None => return Err(failure("an in-memory database cannot be encrypted".to_string())),
+    };
+    let encrypted = is_encrypted(&path);
+    match (encrypted, new) {
+        (_, Some("")) => return Err(failure("the passphrase must not be empty".to_string())),
+        (true, Some(p)) => {
+            conn.pragma_update(None, "rekey", p)?;
+            remember(Some(p));
+            return Ok(conn);
+        }
+        (false, None) => return Err(failure(format!("{} is not encrypted", path.display()))),
+        _ => {}
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".rekey");
+    let tmp = PathBuf::from(tmp);
+    let _ = std::fs::remove_file(&tmp);
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+        params![tmp.to_string_lossy(), new.unwrap_or("")],
+    )?;
+    let exported = conn
+        .query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+        .and_then(|_| conn.execute_batch(&format!("PRAGMA rekeyed.user_version = {};", version)));
+    conn.execute_batch("DETACH DATABASE rekeyed")?;
+    conn.close().map_err(|(_, e)| e)?;
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    let old = PASSPHRASE.lock().unwrap().take();
+    remember(new);
+    let problems = verify_database(&tmp)?;
+    if !problems.is_empty() {
+        remember(old.as_deref());
+        let _ = std::fs::remove_file(&tmp);
+        return Err(failure(format!("the new database failed verification: {}", problems.join("; "))));
+    }
+    std::fs::rename(&tmp, &path).map_err(|e| failure(format!("{}: {}", path.display(), e)))?;
+    let conn = Connection::open(&path)?;
+    apply_key(&conn, &path)?;
+    Ok(conn)
+}
+
+/// Keys `conn` with the remembered passphrase when the file at `path` is
+/// encrypted.
+pub(crate) fn apply_key(conn: &Connection, path: &Path) -> rusqlite::Result<()> {
+    if !is_encrypted(path) {
+        return Ok(());
+    }
+    require_support()?;
+    match PASSPHRASE.lock().unwrap().as_deref() {
+        Some(p) => key(conn, p),
+        None => Err(failure(format!("{} is encrypted; unlock it with its passphrase first", path.display()))),
+    }
+}
+
+/// Keys `dst` (a new, empty database) the same way as `src`, as SQLCipher's
+/// backup API requires.
+pub(crate) fn match_key(dst: &Connection, src: &Connection) -> rusqlite::Result<()> {
+    let Some(src) = src.path().filter(|p| !p.is_empty()) else {
+        return Ok(());
+    };
+    if !is_encrypted(Path::new(src)) {
+        return Ok(());
+    }
+    match PASSPHRASE.lock().unwrap().as_deref() {
+        Some(p) => key(dst, p),
+        None => Err(failure(format!("{} is encrypted; unlock it with its passphrase first", src))),
+    }
+}
+
+fn key(conn: &Connection, passphrase: &str) -> rusqlite::Result<()> {
+    if passphrase.is_empty() {
+        return Err(failure("no passphrase given".to_string()));
+    }
+    conn.pragma_update(None, "key", passphrase)?;
+    // SQLCipher starts logging decryption failures to stderr once the first
+    // key is set; a wrong passphrase is reported through the result instead.
+    conn.pragma_update(None, "cipher_log_level", "NONE")?;
+    // SQLCipher only checks the key when the first page is read.
+    match conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+        Err(e) if e.sqlite_error_code() == Some(ErrorCode::NotADatabase) => {
+            Err(failure("wrong passphrase".to_string()))
+        }
+        other => other,
+    }
+}
+
+fn remember(passphrase: Option<&str>) {
+    *PASSPHRASE.lock().unwrap() = passphrase.map(str::to_string);
+}
+
+fn require_support() -> rusqlite::Result<()> {
+    if ENCRYPTION_SUPPORTED {
+        Ok(())
+    } else {
+        Err(failure("this build has no encryption support (rebuild with `--features sqlcipher`)".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ledger-encryption-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn plain_missing_and_empty_files_are_not_encrypted() {
+        let dir = scratch("header");
+        let plain = dir.join("plain.db");
+        crate::init_db(&Connection::open(&plain).unwrap()).unwrap();
+        assert!(!is_encrypted(&plain));
+        assert!(!is_encrypted(&dir.join("missing.db")));
+        std::fs::write(dir.join("empty.db"), "").unwrap();
+        assert!(!is_encrypted(&dir.join("empty.db")));
+        std::fs::write(dir.join("noise.db"), [7u8; 64]).unwrap();
+        assert!(is_encrypted(&dir.join("noise.db")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // The only test touching the remembered passphrase.
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn rekeyed_database_opens_with_the_new_passphrase_only() {
+        use crate::{EntryFilter, Kind, add_entry, query_entries};
+
+        let dir = scratch("rekey");
+        let path = dir.join("ledger.db");
+        let conn = Connection::open(&path).unwrap();
+        crate::init_db(&conn).unwrap();
+        add_entry(&conn, Kind::Expense, 1200, "food", None).unwrap();
+
+        let conn = change_passphrase(conn, Some("old secret")).unwrap();
+        assert!(is_encrypted(&path));
+        let conn = change_passphrase(conn, Some("new secret")).unwrap();
+        drop(conn);
+
+        let reopened = Connection::open(&path).unwrap();
+        key(&reopened, "new secret").unwrap();
+        assert_eq!(query_entries(&reopened, &EntryFilter::new()).unwrap().len(), 1);
+        let stale = Connection::open(&path).unwrap();
+        assert_eq!(key(&stale, "old secret").unwrap_err().to_string(), "wrong passphrase");
+        drop(stale);
+
+        let conn = change_passphrase(reopened, None).unwrap();
+        assert!(!is_encrypted(&path));
+        assert_eq!(query_entries(&conn, &EntryFilter::new()).unwrap().len(), 1);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,425 @@
+use std::ops::RangeInclusive;
+
+use rusqlite::types::Value;
+use rusqlite::{Connection, params_from_iter};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::backup::failure;
+use crate::dates::{self, YearMonth};
+use crate::settings::{PeriodSettings, load_period_settings};
+use crate::{Entry, Kind};
+
+// Column list shared by every query that builds an `Entry` with `map_entry`.
+pub(crate) const ENTRY_SELECT: &str = "id, kind, amount, category, note, created_at, account, tags";
+
+pub(crate) fn map_entry(row: &rusqlite::Row) -> rusqlite::Result<Entry> {
+    Ok(Entry {
+        id: row.get(0)?,
+        kind: Kind::from_i64(row.get::<_, i64>(1)?),
+        amount: row.get(2)?,
+        category: row.get(3)?,
+        note: row.get::<_, Option<String>>(4)?,
+        created_at: row.get(5)?,
+        account: row.get::<_, Option<String>>(6)?,
+        tags: split_tags(row.get::<_, Option<String>>(7)?.as_deref()),
+    })
+}
+
+// Tags are stored as one comma separated column so they travel with the row
+// through the audit log and undo/redo.
+pub(crate) fn join_tags(tags: &[String]) -> Option<String> {
+    let tags: Vec<&str> = tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()).collect();
+    if tags.is_empty() { None } else { Some(tags.join(",")) }
+}
+
+pub(crate) fn split_tags(s: Option<&str>) -> Vec<String> {
+    s.unwrap_or("")
+        .split(',')
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .collect()
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum SortOrder {
+    DateAsc,
+    #[default]
+    DateDesc,
+    AmountAsc,
+    AmountDesc,
+}
+
+impl SortOrder {
+    fn sql(self) -> &'static str {
+        match self {
+            SortOrder::DateAsc => "created_at ASC, id ASC",
+            SortOrder::DateDesc => "created_at DESC, id DESC",
+            SortOrder::AmountAsc => "amount ASC, id ASC",
+            SortOrder::AmountDesc => "amount DESC, id DESC",
+        }
+    }
+}
+
+/// Criteria for `query_entries`. Every field is optional; an empty filter
+/// matches all live (non-trashed) entries, newest first.
+#[derive(Clone, Debug, Default)]
+pub struct EntryFilter {
+    pub from_date: Option<NaiveDate>,
+    pub to_date: Option<NaiveDate>,
+    /// Inclusive month range; month boundaries follow the ledger's period settings.
+    pub months: Option<(YearMonth, YearMonth)>,
+    pub kinds: Vec<Kind>,
+    pub categories: Vec<String>,
+    pub min_amount: Option<i64>,
+    pub max_amount: Option<i64>,
+    pub note_contains: Option<String>,
+    pub account: Option<String>,
+    pub tags: Vec<String>,
+    pub order: SortOrder,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inclusive date bounds.
+    pub fn dates(mut self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.from_date = Some(from);
+        self.to_date = Some(to);
+        self
+    }
+
+    pub fn month(self, ym: YearMonth) -> Self {
+        self.months(ym, ym)
+    }
+
+    pub fn months(mut self, start: YearMonth, end: YearMonth) -> Self {
+        self.months = Some((start, end));
+        self
+    }
+
+    pub fn kind(mut self, kind: Kind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn category(mut self, category: &str) -> Self {
+        self.categories.push(category.to_string());
+        self
+    }
+
+    pub fn min_amount(mut self, amount: i64) -> Self {
+        self.min_amount = Some(amount);
+        self
+    }
+
+    pub fn max_amount(mut self, amount: i64) -> Self {
+        self.max_amount = Some(amount);
+        self
+    }
+
+    pub fn note_contains(mut self, text: &str) -> Self {
+        self.note_contains = Some(text.to_string());
+        self
+    }
+
+    pub fn account(mut self, account: &str) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    /// Entries must carry every tag given.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    pub fn order(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: i64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    fn where_clause(&self, settings: &PeriodSettings, values: &mut Vec<Value>) -> String {
+        let mut conds = vec!["deleted_at IS NULL".to_string()];
+        if let Some((start, end)) = self.months {
+            values.push(Value::Text(dates::day_start(settings.month_start(start))));
+            conds.push(format!("created_at >= ?{}", values.len()));
+            values.push(Value::Text(dates::day_start(settings.month_start(end.succ()))));
+            conds.push(format!("created_at < ?{}", values.len()));
+        }
+        if let Some(from) = self.from_date {
+            values.push(Value::Text(dates::day_start(from)));
+            conds.push(format!("created_at >= ?{}", values.len()));
+        }
+        if let Some(to) = self.to_date {
+            // Only the last representable day has no successor, and every
+            // entry is on or before it, so that bound needs no condition.
+            if let Some(next_day) = to.succ_opt() {
+                values.push(Value::Text(dates::day_start(next_day)));
+                conds.push(format!("created_at < ?{}", values.len()));
+            }
+        }
+        if !self.kinds.is_empty() {
+            let mut placeholders = Vec::new();
+            for k in &self.kinds {
+                values.push(Value::Integer(k.to_i64()));
+                placeholders.push(format!("?{}", values.len()));
+            }
+            conds.push(format!("kind IN ({})", placeholders.join(", ")));
+        }
+        if !self.categories.is_empty() {
+            let mut placeholders = Vec::new();
+            for c in &self.categories {
+                values.push(Value::Text(c.clone()));
+                placeholders.push(format!("?{}", values.len()));
+            }
+            conds.push(format!("category IN ({})", placeholders.join(", ")));
+        }
+        if let Some(min) = self.min_amount {
+            values.push(Value::Integer(min));
+            conds.push(format!("amount >= ?{}", values.len()));
+        }
+        if let Some(max) = self.max_amount {
+            values.push(Value::Integer(max));
+            conds.push(format!("amount <= ?{}", values.len()));
+        }
+        if let Some(text) = &self.note_contains {
+            values.push(Value::Text(text.clone()));
+            conds.push(format!("instr(lower(coalesce(note, '')), lower(?{})) > 0", values.len()));
+        }
+        if let Some(account) = &self.account {
+            values.push(Value::Text(account.clone()));
+            conds.push(format!("account = ?{}", values.len()));
+        }
+        for t in &self.tags {
+            values.push(Value::Text(t.clone()));
+            conds.push(format!(
+                "instr(',' || coalesce(tags, '') || ',', ',' || ?{} || ',') > 0",
+                values.len()
+            ));
+        }
+        conds.join(" AND ")
+    }
+}
+
+/// Position after the last entry of a page, in the sort order of the filter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryCursor {
+    pub created_at: NaiveDateTime,
+    pub amount: i64,
+    pub id: i64,
+}
+
+impl EntryCursor {
+    pub fn after(e: &Entry) -> Self {
+        EntryCursor {
+            created_at: e.created_at,
+            amount: e.amount,
+            id: e.id,
+        }
+    }
+
+    // Keyset condition: rows strictly after the cursor in `order`.
+    fn condition(&self, order: SortOrder, values: &mut Vec<Value>) -> String {
+        let (column, value, op) = match order {
+            SortOrder::DateAsc => ("created_at", Value::Text(dates::format_timestamp(self.created_at)), ">"),
+            SortOrder::DateDesc => ("created_at", Value::Text(dates::format_timestamp(self.created_at)), "<"),
+            SortOrder::AmountAsc => ("amount", Value::Integer(self.amount), ">"),
+            SortOrder::AmountDesc => ("amount", Value::Integer(self.amount), "<"),
+        };
+        values.push(value);
+        values.push(Value::Integer(self.id));
+        format!("({}, id) {} (?{}, ?{})", column, op, values.len() - 1, values.len())
+    }
+}
+
+#[derive(Debug)]
+pub struct EntryPage {
+    pub entries: Vec<Entry>,
+    /// `None` on the last page.
+    pub next: Option<EntryCursor>,
+}
+
+/// Calls `f` for every entry matching `filter`, one row at a time,
+/// without collecting the result set.
+pub fn for_each_entry<E, F>(conn: &Connection, filter: &EntryFilter, f: F) -> Result<(), E>
+where
+    E: From<rusqlite::Error>,
+    F: FnMut(Entry) -> Result<(), E>,
+{
+    stream(conn, filter, None, f)
+}
+
+fn stream<E, F>(
+    conn: &Connection,
+    filter: &EntryFilter,
+    cursor: Option<&EntryCursor>,
+    mut f: F,
+) -> Result<(), E>
+where
+    E: From<rusqlite::Error>,
+    F: FnMut(Entry) -> Result<(), E>,
+{
+    let settings = if filter.months.is_some() {
+        load_period_settings(conn)?
+    } else {
+        PeriodSettings::default()
+    };
+    let mut values = Vec::new();
+    let mut conds = filter.where_clause(&settings, &mut values);
+    if let Some(c) = cursor {
+        conds = format!("{} AND {}", conds, c.condition(filter.order, &mut values));
+    }
+    let mut sql = format!(
+        "SELECT {} FROM entries WHERE {} ORDER BY {}",
+        ENTRY_SELECT,
+        conds,
+        filter.order.sql()
+    );
+    if filter.limit.is_some() || filter.offset.is_some() {
+        values.push(Value::Integer(filter.limit.unwrap_or(-1)));
+        values.push(Value::Integer(filter.offset.unwrap_or(0)));
+        sql.push_str(&format!(" LIMIT ?{} OFFSET ?{}", values.len() - 1, values.len()));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params_from_iter(values))?;
+    while let Some(row) = rows.next()? {
+        f(map_entry(row)?)?;
+    }
+    Ok(())
+}
+
+pub fn query_entries(conn: &Connection, filter: &EntryFilter) -> rusqlite::Result<Vec<Entry>> {
+    let mut v = Vec::new();
+    for_each_entry(conn, filter, |e| {
+        v.push(e);
+        Ok::<_, rusqlite::Error>(())
+    })?;
+    Ok(v)
+}
+
+/// Page sizes `list_page` accepts.
+pub const PAGE_SIZES: RangeInclusive<i64> = 1..=1000;
+
+/// Returns up to `page_size` entries following `after` (or from the start).
+/// Pages are keyed on the sort column and id, so they stay stable while rows
+/// are added or removed elsewhere. `filter.limit` and `filter.offset` are ignored.
+/// `page_size` must be within `PAGE_SIZES`.
+pub fn list_page(
+    conn: &Connection,
+    filter: &EntryFilter,
+    after: Option<&EntryCursor>,
+    page_size: i64,
+) -> rusqlite::Result<EntryPage> {
+    if !PAGE_SIZES.contains(&page_size) {
+        return Err(failure(format!(
+            "page size must be {} to {}, got {}",
+            PAGE_SIZES.start(),
+            PAGE_SIZES.end(),
+            page_size
+        )));
+    }
+    let mut f = filter.clone();
+    f.offset = None;
+    // One extra row tells us whether another page follows.
+    f.limit = Some(page_size + 1);
+
+    let mut entries = Vec::new();
+    stream(conn, &f, after, |e| {
+        entries.push(e);
+        Ok::<_, rusqlite::Error>(())
+    })?;
+
+    let next = if entries.len() as i64 > page_size {
+        entries.truncate(page_size as usize);
+        entries.last().map(EntryCursor::after)
+    } else {
+        None
+    };
+    Ok(EntryPage { entries, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewEntry, init_db, insert_entry};
+
+    fn ledger() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        // Repeated timestamps and amounts, so pages split inside runs of ties.
+        for i in 0..23 {
+            let mut e = NewEntry::new(Kind::Expense, 100 * (i % 4 + 1), "food");
+            e.created_at = NaiveDateTime::parse_from_str(&format!("2025-08-{:02} 12:00:00", i % 5 + 1), "%Y-%m-%d %H:%M:%S").ok();
+            insert_entry(&conn, &e).unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn pages_visit_every_entry_once() {
+        let conn = ledger();
+        for order in [SortOrder::DateAsc, SortOrder::DateDesc, SortOrder::AmountAsc, SortOrder::AmountDesc] {
+            let mut filter = EntryFilter::new();
+            filter.order = order;
+            let all: Vec<i64> = query_entries(&conn, &filter).unwrap().iter().map(|e| e.id).collect();
+            for page_size in [1, 4, 7, 23, 100] {
+                let mut seen = Vec::new();
+                let mut after = None;
+                loop {
+                    let page = list_page(&conn, &filter, after.as_ref(), page_size).unwrap();
+                    assert!(page.entries.len() as i64 <= page_size);
+                    seen.extend(page.entries.iter().map(|e| e.id));
+                    match page.next {
+                        Some(c) => after = Some(c),
+                        None => break,
+                    }
+                }
+                assert_eq!(seen, all, "{:?} with page size {}", order, page_size);
+            }
+        }
+    }
+
+    #[test]
+    fn page_sizes_outside_the_range_are_rejected() {
+        let conn = ledger();
+        for page_size in [0, -1, i64::MIN, 1001, i64::MAX] {
+            assert!(list_page(&conn, &EntryFilter::new(), None, page_size).is_err(), "{}", page_size);
+        }
+        assert_eq!(list_page(&conn, &EntryFilter::new(), None, 1000).unwrap().entries.len(), 23);
+    }
+
+    #[test]
+    fn to_date_bounds_the_range() {
+        let conn = ledger();
+        let count = |from: NaiveDate, to: NaiveDate| {
+            let mut filter = EntryFilter::new();
+            filter.from_date = Some(from);
+            filter.to_date = Some(to);
+            query_entries(&conn, &filter).unwrap().len()
+        };
+        let day = |d: u32| NaiveDate::from_ymd_opt(2025, 8, d).unwrap();
+        // Entries fall on 2025-08-01..05, five of them on the 1st, 2nd and 3rd.
+        assert_eq!(count(day(1), day(3)), 15);
+        assert_eq!(count(day(3), day(3)), 5);
+        // The last day chrono can represent has no next day to bound by.
+        assert_eq!(count(day(3), NaiveDate::MAX), 13);
+        assert_eq!(count(NaiveDate::MIN, NaiveDate::MAX), 23);
+    }
+}
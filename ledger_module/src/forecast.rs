@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use chrono::{Days, NaiveDate, NaiveDateTime};
+use rusqlite::{Connection, params};
+
+use crate::backup::failure;
+use crate::recurring::{RecurringRule, list_recurring};
+use crate::settings::load_period_settings;
+use crate::{Kind, YearMonth, category_totals_by_kind_in_range, dates, entries_in_range};
+
+/// Expected monthly amount of a category apart from its recurring rules, taken
+/// from the trailing average of its entries no rule accounts for.
+#[derive(Debug)]
+pub struct CategoryAverage {
+    pub kind: Kind,
+    pub category: String,
+    pub monthly: i64,
+}
+
+#[derive(Debug)]
+pub struct ForecastMonth {
+    pub month: YearMonth,
+    /// First forecast day of the month; later than the month start for the
+    /// month in progress.
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub income: i64,
+    pub expense: i64,
+    /// Projected balance at the end of the month.
+    pub balance: i64,
+}
+
+#[derive(Debug)]
+pub struct Forecast {
+    /// Last day of actual data; the forecast starts the day after.
+    pub as_of: NaiveDate,
+    /// Balance of every entry up to and including `as_of`.
+    pub opening_balance: i64,
+    /// Complete months the averages were taken from, if any.
+    pub history: Option<(YearMonth, YearMonth)>,
+    pub averages: Vec<CategoryAverage>,
+    pub rules: Vec<RecurringRule>,
+    /// The rest of the current month, then one row per forecast month.
+    pub months: Vec<ForecastMonth>,
+    /// First day the projected balance is below zero.
+    pub negative_on: Option<NaiveDate>,
+}
+
+/// Accepted values of `forecast`'s `months` and `history_months`.
+pub const FORECAST_MONTHS: RangeInclusive<u32> = 1..=120;
+pub const FORECAST_HISTORY_MONTHS: RangeInclusive<u32> = 0..=120;
+
+/// Projects income, expense and balance from the day after `as_of` to the end
+/// of the `months`-th month after the current one.
+///
+/// Recurring rules are projected on the days they fall due. Each category is
+/// also projected at its average over the last `history_months` complete
+/// months, spread evenly over the days of each month; past entries matching a
+/// rule (same kind, category and amount) are left out of that average, since
+/// the rule already accounts for them.
+pub fn forecast(
+    conn: &Connection,
+    as_of: NaiveDate,
+    months: u32,
+    history_months: u32,
+) -> rusqlite::Result<Forecast> {
+    for (name, value, range) in [("months", months, FORECAST_MONTHS), ("history months", history_months, FORECAST_HISTORY_MONTHS)] {
+        if !range.contains(&value) {
+            return Err(failure(format!(
+                "forecast {} must be {} to {}, got {}",
+                name,
+                range.start(),
+                range.end(),
+                value
+            )));
+        }
+    }
+    let settings = load_period_settings(conn)?;
+    let current = settings.month_of(as_of);
+    let from = as_of.succ_opt().expect("not the last representable day");
+
+    let rules = list_recurring(conn)?;
+
+    // Only count months since the first entry, so a young ledger is not
+    // averaged down by months it has no data for.
+    let mut history_start = current.add_months(-(history_months as i32));
+    if let Some(first) = first_entry_at(conn)? {
+        history_start = history_start.max(settings.month_of(first.date()));
+    }
+    let history_end = current.pred();
+    let history = (history_months > 0 && history_start <= history_end).then_some((history_start, history_end));
+
+    let mut averages = Vec::new();
+    if let Some((start, end)) = history {
+        let n = start.through(end).len() as f64;
+        let mut from_rules: HashMap<(i64, String), i64> = HashMap::new();
+        if !rules.is_empty() {
+            for e in entries_in_range(conn, start, end)? {
+                if rules.iter().any(|r| r.kind == e.kind && r.category == e.category && r.amount == e.amount) {
+                    *from_rules.entry((e.kind.to_i64(), e.category)).or_default() += e.amount;
+                }
+            }
+        }
+        for kind in [Kind::Income, Kind::Expense] {
+            for t in category_totals_by_kind_in_range(conn, start, end, kind)? {
+                let total = t.total - from_rules.get(&(kind.to_i64(), t.category.clone())).copied().unwrap_or(0);
+                if total > 0 {
+                    averages.push(CategoryAverage {
+                        kind,
+                        category: t.category,
+                        monthly: (total as f64 / n).round() as i64,
+                    });
+                }
+            }
+        }
+    }
+    let monthly = |kind: Kind| -> i64 { averages.iter().filter(|a| a.kind == kind).map(|a| a.monthly).sum() };
+    let (avg_income, avg_expense) = (monthly(Kind::Income), monthly(Kind::Expense));
+
+    let opening_balance = balance_before(conn, from)?;
+    let mut balance = opening_balance;
+    let mut negative_on = None;
+    let mut rows = Vec::new();
+    for ym in current.through(current.add_months(months as i32)) {
+        let (start, end) = (settings.month_start(ym), settings.month_end(ym));
+        let days = (end - start).num_days() + 1;
+        let mut row = ForecastMonth {
+            month: ym,
+            start: start.max(from),
+            end,
+            income: 0,
+            expense: 0,
+            balance: 0,
+        };
+        if row.start > end {
+            continue;
+        }
+        for i in 0..days {
+            let day = start + Days::new(i as u64);
+            if day < from {
+                continue;
+            }
+            let mut income = share(avg_income, i, days);
+            let mut expense = share(avg_expense, i, days);
+            for r in rules.iter().filter(|r| r.is_due(day)) {
+                match r.kind {
+                    Kind::Income => income += r.amount,
+                    Kind::Expense => expense += r.amount,
+                }
+            }
+            row.income += income;
+            row.expense += expense;
+            balance += income - expense;
+            if balance < 0 && negative_on.is_none() {
+                negative_on = Some(day);
+            }
+        }
+        row.balance = balance;
+        rows.push(row);
+    }
+
+    Ok(Forecast {
+        as_of,
+        opening_balance,
+        history,
+        averages,
+        rules,
+        months: rows,
+        negative_on,
+    })
+}
+
+// The part of a monthly `total` falling on day `i` of `days`. The parts add
+// up to `total` exactly.
+fn share(total: i64, i: i64, days: i64) -> i64 {
+    total * (i + 1) / days - total * i / days
+}
+
+pub(crate) fn first_entry_at(conn: &Connection) -> rusqlite::Result<Option<NaiveDateTime>> {
+    conn.query_row(
+        "SELECT MIN(created_at) FROM entries WHERE deleted_at IS NULL",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// Income minus expense of every entry before `until`.
+fn balance_before(conn: &Connection, until: NaiveDate) -> rusqlite::Result<i64> {
+    conn.query_row(
+        r#"
+        SELECT coalesce(SUM(CASE WHEN kind = 1 THEN amount ELSE -amount END), 0)
+        FROM entries
+        WHERE deleted_at IS NULL AND created_at < ?1
+        "#,
+        params![dates::day_start(until)],
+        |row| row.get(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recurring::{NewRecurring, add_recurring};
+    use crate::{NewEntry, init_db, insert_entry};
+
+    fn ledger() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn
+    }
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn spend(conn: &Connection, amount: i64, category: &str, at: &str) {
+        let mut e = NewEntry::new(Kind::Expense, amount, category);
+        e.created_at = NaiveDateTime::parse_from_str(&format!("{} 12:00:00", at), "%Y-%m-%d %H:%M:%S").ok();
+        insert_entry(conn, &e).unwrap();
+    }
+
+    #[test]
+    fn rules_add_to_the_average_of_other_spending_in_their_category() {
+        let conn = ledger();
+        add_recurring(&conn, &NewRecurring::new(Kind::Expense, 1500, "Entertainment", 10, "2025-07".parse().unwrap()))
+            .unwrap();
+        for (month, ad_hoc) in [("2025-07", 2000), ("2025-08", 3000), ("2025-09", 4000)] {
+            spend(&conn, 1500, "Entertainment", &format!("{}-10", month));
+            spend(&conn, ad_hoc, "Entertainment", &format!("{}-20", month));
+        }
+
+        let f = forecast(&conn, NaiveDate::from_ymd_opt(2025, 10, 31).unwrap(), 1, 3).unwrap();
+        assert_eq!(f.history, Some(("2025-07".parse().unwrap(), "2025-09".parse().unwrap())));
+        assert_eq!(f.averages.len(), 1);
+        assert_eq!(f.averages[0].category, "Entertainment");
+        assert_eq!(f.averages[0].monthly, 3000);
+        let november = f.months.iter().find(|m| m.month == "2025-11".parse().unwrap()).unwrap();
+        assert_eq!(november.expense, 3000 + 1500);
+    }
+
+    #[test]
+    fn months_run_across_the_year_boundary() {
+        let conn = ledger();
+        add_recurring(&conn, &NewRecurring::new(Kind::Income, 1000, "Salary", 1, ym("2025-01"))).unwrap();
+        for month in ["2025-08", "2025-09", "2025-10"] {
+            spend(&conn, 3000, "Food", &format!("{}-05", month));
+        }
+
+        let f = forecast(&conn, date("2025-11-15"), 2, 3).unwrap();
+        assert_eq!(f.opening_balance, -9000);
+        let rows: Vec<(YearMonth, NaiveDate, NaiveDate, i64, i64, i64)> = f
+            .months
+            .iter()
+            .map(|m| (m.month, m.start, m.end, m.income, m.expense, m.balance))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                // Days 16-30 of November: half the average, and the rule's day has passed.
+                (ym("2025-11"), date("2025-11-16"), date("2025-11-30"), 0, 1500, -10500),
+                (ym("2025-12"), date("2025-12-01"), date("2025-12-31"), 1000, 3000, -12500),
+                (ym("2026-01"), date("2026-01-01"), date("2026-01-31"), 1000, 3000, -14500),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_lengths_are_rejected() {
+        let conn = ledger();
+        let today = date("2025-11-15");
+        for (months, history) in [(0, 3), (121, 3), (2_000_000_000, 3), (6, 121), (6, u32::MAX)] {
+            assert!(forecast(&conn, today, months, history).is_err(), "{} {}", months, history);
+        }
+        assert_eq!(forecast(&conn, today, 120, 0).unwrap().months.len(), 121);
+        assert_eq!(forecast(&conn, today, 1, 120).unwrap().months.len(), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::{NewEntry, insert_entry, run_operation};
+
+pub(crate) const SCHEMA: &str = r#"
+    CREATE TABLE import_refs (
+        source      TEXT NOT NULL,
+        ref         TEXT NOT NULL,
+        entry_id    INTEGER NOT NULL,
+        imported_at TEXT NOT NULL DEFAULT (datetime('now','localtime')),
+        PRIMARY KEY (source, ref)
+    );
+"#;
+
+/// An entry read from another app's export, with the reference that
+/// identifies it in that source (a transaction id, or a fingerprint of the
+/// row when the source has none).
+#[derive(Debug, Clone)]
+pub struct ImportItem {
+    pub entry: NewEntry,
+    pub reference: String,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportResult {
+    pub added: usize,
+    pub skipped: usize,
+}
+
+/// For each item, whether it was imported from `source` before (or repeats
+/// an earlier item of the same batch). An undone import does not count.
+pub fn already_imported(conn: &Connection, source: &str, items: &[ImportItem]) -> rusqlite::Result<Vec<bool>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT 1 FROM import_refs r JOIN entries e ON e.id = r.entry_id
+        WHERE r.source = ?1 AND r.ref = ?2
+        "#,
+    )?;
+    let mut seen = HashSet::new();
+    let mut out = Vec::with_capacity(items.len());
+    for item in items {
+        let known = stmt
+            .query_row(params![source, item.reference], |_| Ok(()))
+            .optional()?
+            .is_some();
+        out.push(known || !seen.insert(item.reference.as_str()));
+    }
+    Ok(out)
+}
+
+/// Inserts the items not imported from `source` before, as one undoable
+/// operation labelled `label`.
+pub fn import_entries(
+    conn: &Connection,
+    source: &str,
+    label: &str,
+    items: &[ImportItem],
+) -> rusqlite::Result<ImportResult> {
+    run_operation(conn, label, |tx| {
+        let known = already_imported(tx, source, items)?;
+        let mut result = ImportResult::default();
+        for (item, known) in items.iter().zip(known) {
+            if known {
+                result.skipped += 1;
+                continue;
+            }
+            let id = insert_entry(tx, &item.entry)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO import_refs (source, ref, entry_id) VALUES (?1, ?2, ?3)",
+                params![source, item.reference, id],
+            )?;
+            result.added += 1;
+        }
+        Ok(result)
+    })
+}
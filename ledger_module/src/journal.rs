@@ -0,0 +1,261 @@
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::audit;
+
+// Every user-level change (one add, one delete, one whole import, ...) is an
+// operation. Its audit rows are the change set that undo/redo replays.
+pub(crate) const SCHEMA: &str = r#"
+    CREATE TABLE operations (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT,
+        label      TEXT NOT NULL,
+        actor      TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT (datetime('now','localtime')),
+        state      TEXT NOT NULL DEFAULT 'open'
+                   CHECK(state IN ('open', 'done', 'undone', 'discarded'))
+    );
+    ALTER TABLE audit_log ADD COLUMN operation_id INTEGER REFERENCES operations(id);
+"#;
+
+#[derive(Debug)]
+pub struct Operation {
+    pub id: i64,
+    pub label: String,
+    pub actor: String,
+    pub created_at: String,
+}
+
+/// Runs `f` as a single undoable operation inside one transaction.
+/// Calls made while another operation is already running join that operation,
+/// so a bulk import wrapping many `add_entry` calls is undone as a unit.
+pub fn run_operation<T>(
+    conn: &Connection,
+    label: &str,
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    if !conn.is_autocommit() {
+        return f(conn);
+    }
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO operations (label, actor) VALUES (?1, ?2)",
+        params![label, audit::current_actor()],
+    )?;
+    let op_id = tx.last_insert_rowid();
+
+    let out = f(&tx)?;
+
+    let changes: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM audit_log WHERE operation_id = ?1",
+        params![op_id],
+        |row| row.get(0),
+    )?;
+    if changes == 0 {
+        tx.execute("DELETE FROM operations WHERE id = ?1", params![op_id])?;
+    } else {
+        // A new change invalidates whatever could have been redone.
+        tx.execute("UPDATE operations SET state = 'discarded' WHERE state = 'undone'", [])?;
+        tx.execute("UPDATE operations SET state = 'done' WHERE id = ?1", params![op_id])?;
+    }
+    tx.commit()?;
+    Ok(out)
+}
+
+/// Reverts the most recent operation. Returns `None` when there is nothing to undo.
+pub fn undo(conn: &Connection) -> rusqlite::Result<Option<Operation>> {
+    replay(conn, "done", "undone", "undo")
+}
+
+/// Re-applies the most recently undone operation. Returns `None` when there is nothing to redo.
+pub fn redo(conn: &Connection) -> rusqlite::Result<Option<Operation>> {
+    replay(conn, "undone", "done", "redo")
+}
+
+fn replay(conn: &Connection, from: &str, to: &str, action: &str) -> rusqlite::Result<Option<Operation>> {
+    let tx = conn.unchecked_transaction()?;
+    // Undo takes the newest done operation; redo takes the oldest undone one,
+    // i.e. the one undone last.
+    let order = if action == "undo" { "DESC" } else { "ASC" };
+    let op = tx
+        .query_row(
+            &format!(
+                "SELECT id, label, actor, created_at FROM operations WHERE state = ?1 ORDER BY id {} LIMIT 1",
+                order
+            ),
+            params![from],
+            |row| {
+                Ok(Operation {
+                    id: row.get(0)?,
+                    label: row.get(1)?,
+                    actor: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    let Some(op) = op else {
+        return Ok(None);
+    };
+
+    let mut stmt = tx.prepare(&format!(
+        "SELECT entry_id, before, after FROM audit_log WHERE operation_id = ?1 ORDER BY id {}",
+        order
+    ))?;
+    let changes = stmt
+        .query_map(params![op.id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+
+    for (entry_id, before, after) in changes {
+        let target = if action == "undo" { before } else { after };
+        let current = audit::snapshot(&tx, entry_id)?;
+        audit::apply_snapshot(&tx, entry_id, target.as_deref())?;
+        audit::record(&tx, entry_id, action, current.as_deref(), target.as_deref())?;
+    }
+    tx.execute(
+        "UPDATE operations SET state = ?2 WHERE id = ?1",
+        params![op.id, to],
+    )?;
+    tx.commit()?;
+    Ok(Some(op))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImportItem, Kind, NewEntry, add_entry, already_imported, delete_entry, import_entries, init_db, update_entry};
+
+    fn ledger() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn
+    }
+
+    // Every row, trashed ones included, as `id kind amount category note [trashed]`.
+    fn rows(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT id, kind, amount, category, note, deleted_at IS NOT NULL FROM entries ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| {
+            Ok(format!(
+                "{} {} {} {} {}{}",
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                if row.get::<_, bool>(5)? { " [trashed]" } else { "" }
+            ))
+        })
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+    }
+
+    fn label(op: Option<Operation>) -> String {
+        op.map(|o| o.label).unwrap_or_default()
+    }
+
+    #[test]
+    fn add_edit_and_delete_undo_and_redo() {
+        let conn = ledger();
+        let id = add_entry(&conn, Kind::Expense, 100, "food", None).unwrap();
+        let added = rows(&conn);
+        update_entry(&conn, id, Kind::Expense, 250, "dining", Some("lunch")).unwrap();
+        let edited = rows(&conn);
+        delete_entry(&conn, id).unwrap();
+        let deleted = rows(&conn);
+        assert_eq!(added, ["1 0 100 food "]);
+        assert_eq!(edited, ["1 0 250 dining lunch"]);
+        assert_eq!(deleted, ["1 0 250 dining lunch [trashed]"]);
+
+        assert_eq!(label(undo(&conn).unwrap()), "delete entry 1");
+        assert_eq!(rows(&conn), edited);
+        assert_eq!(label(undo(&conn).unwrap()), "edit entry 1");
+        assert_eq!(rows(&conn), added);
+        assert_eq!(label(undo(&conn).unwrap()), "add food 100");
+        assert!(rows(&conn).is_empty());
+        assert!(undo(&conn).unwrap().is_none());
+
+        assert_eq!(label(redo(&conn).unwrap()), "add food 100");
+        assert_eq!(rows(&conn), added);
+        assert_eq!(label(redo(&conn).unwrap()), "edit entry 1");
+        assert_eq!(rows(&conn), edited);
+        assert_eq!(label(redo(&conn).unwrap()), "delete entry 1");
+        assert_eq!(rows(&conn), deleted);
+        assert!(redo(&conn).unwrap().is_none());
+    }
+
+    #[test]
+    fn an_import_is_undone_as_one_operation() {
+        let conn = ledger();
+        add_entry(&conn, Kind::Income, 5000, "salary", None).unwrap();
+        let items: Vec<ImportItem> = (1..=3)
+            .map(|i| ImportItem { entry: NewEntry::new(Kind::Expense, 100 * i, "food"), reference: format!("row {}", i) })
+            .collect();
+        let r = import_entries(&conn, "test", "import test.csv", &items).unwrap();
+        assert_eq!(r.added, 3);
+        let imported = rows(&conn);
+        assert_eq!(imported.len(), 4);
+
+        assert_eq!(label(undo(&conn).unwrap()), "import test.csv");
+        assert_eq!(rows(&conn), ["1 1 5000 salary "]);
+        // Undone rows can be imported again.
+        assert_eq!(already_imported(&conn, "test", &items).unwrap(), [false, false, false]);
+
+        assert_eq!(label(redo(&conn).unwrap()), "import test.csv");
+        assert_eq!(rows(&conn), imported);
+        assert_eq!(already_imported(&conn, "test", &items).unwrap(), [true, true, true]);
+    }
+
+    #[test]
+    fn a_new_change_clears_redo() {
+        let conn = ledger();
+        add_entry(&conn, Kind::Expense, 100, "food", None).unwrap();
+        add_entry(&conn, Kind::Expense, 200, "fun", None).unwrap();
+        undo(&conn).unwrap();
+        add_entry(&conn, Kind::Expense, 300, "travel", None).unwrap();
+
+        assert!(redo(&conn).unwrap().is_none());
+        assert_eq!(rows(&conn), ["1 0 100 food ", "3 0 300 travel "]);
+        let discarded: i64 = conn
+            .query_row("SELECT COUNT(*) FROM operations WHERE state = 'discarded'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(discarded, 1);
+        // Undo walks back past the cleared change.
+        assert_eq!(label(undo(&conn).unwrap()), "add travel 300");
+        assert_eq!(label(undo(&conn).unwrap()), "add food 100");
+    }
+
+    #[test]
+    fn undo_survives_reopening_the_database() {
+        let path = std::env::temp_dir().join(format!("ledger-journal-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let open = || {
+            let conn = Connection::open(&path).unwrap();
+            init_db(&conn).unwrap();
+            conn
+        };
+
+        let conn = open();
+        let id = add_entry(&conn, Kind::Expense, 100, "food", None).unwrap();
+        update_entry(&conn, id, Kind::Expense, 120, "food", Some("tax")).unwrap();
+        drop(conn);
+
+        let conn = open();
+        assert_eq!(label(undo(&conn).unwrap()), "edit entry 1");
+        assert_eq!(rows(&conn), ["1 0 100 food "]);
+        drop(conn);
+
+        let conn = open();
+        assert_eq!(label(redo(&conn).unwrap()), "edit entry 1");
+        assert_eq!(rows(&conn), ["1 0 120 food tax"]);
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
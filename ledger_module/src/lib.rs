@@ -1,4 +1,50 @@
-use rusqlite::{Connection, params};
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::params;
+
+pub use rusqlite::{Connection, Error};
+
+mod anomalies;
+mod audit;
+mod backup;
+mod compare;
+mod dates;
+mod doctor;
+mod encryption;
+mod filter;
+mod forecast;
+mod imports;
+mod journal;
+mod periods;
+mod pivot;
+mod recurring;
+mod settings;
+mod stats;
+mod subscriptions;
+
+pub use anomalies::{Anomalies, Duplicate, Outlier, Spike, find_anomalies};
+pub use audit::{AuditRecord, current_actor, entry_history, recent_changes};
+pub use backup::{
+    BackupRetention, auto_backup, backup_database, backup_dir, list_backups, load_backup_retention,
+    restore_database, save_backup_retention, verify_database,
+};
+pub use compare::{CategoryDelta, Delta, PeriodComparison, compare_periods};
+pub use dates::{InvalidYearMonth, YearMonth};
+pub use doctor::{Problem, diagnose, repair, vacuum};
+pub use encryption::{ENCRYPTION_SUPPORTED, change_passphrase, is_encrypted, is_unlocked, unlock};
+pub use filter::{
+    EntryCursor, EntryFilter, EntryPage, PAGE_SIZES, SortOrder, for_each_entry, list_page, query_entries,
+};
+pub use forecast::{CategoryAverage, FORECAST_HISTORY_MONTHS, FORECAST_MONTHS, Forecast, ForecastMonth, forecast};
+pub use imports::{ImportItem, ImportResult, already_imported, import_entries};
+pub use journal::{Operation, redo, run_operation, undo};
+pub use periods::{Granularity, PeriodTotal, period_summaries};
+pub use pivot::{PivotRow, PivotTable, category_month_pivot};
+pub use recurring::{NewRecurring, RecurringRule, add_recurring, list_recurring, remove_recurring};
+pub use settings::{
+    PeriodSettings, get_setting, load_period_settings, save_period_settings, set_setting,
+};
+pub use stats::{CategoryStats, MonthStats, Stats, stats};
+pub use subscriptions::{Interval, Subscription, detect_subscriptions};
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Kind {
@@ -17,18 +63,52 @@ impl Kind {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Entry {
     pub id: i64,
     pub kind: Kind,
     pub amount: i64,
     pub category: String,
     pub note: Option<String>,
-    pub created_at: String,
+    pub created_at: NaiveDateTime,
+    pub account: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Values for `insert_entry`. `created_at` defaults to now.
+#[derive(Debug, Clone)]
+pub struct NewEntry {
+    pub kind: Kind,
+    pub amount: i64,
+    pub category: String,
+    pub note: Option<String>,
+    pub account: Option<String>,
+    pub tags: Vec<String>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl NewEntry {
+    pub fn new(kind: Kind, amount: i64, category: &str) -> Self {
+        NewEntry {
+            kind,
+            amount,
+            category: category.to_string(),
+            note: None,
+            account: None,
+            tags: Vec::new(),
+            created_at: None,
+        }
+    }
 }
 
+/// The ledger database, relative to the working directory.
+pub const DB_PATH: &str = "ledger.db";
+
+/// Opens the ledger database. An encrypted one must be unlocked first.
 pub fn open_db() -> rusqlite::Result<Connection> {
-    Connection::open("ledger.db")
+    let conn = Connection::open(DB_PATH)?;
+    encryption::apply_key(&conn, std::path::Path::new(DB_PATH))?;
+    Ok(conn)
 }
 
 pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
@@ -43,7 +123,50 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             created_at TEXT NOT NULL DEFAULT (datetime('now','localtime'))
         );
         "#,
-    )
+    )?;
+    migrate(conn)
+}
+
+// Schema changes after the initial `entries` table, applied in order.
+// `PRAGMA user_version` records how many of them a database has seen.
+const MIGRATIONS: &[&str] = &[
+    // 1: soft delete (trash)
+    "ALTER TABLE entries ADD COLUMN deleted_at TEXT;",
+    // 2: append-only audit log
+    audit::SCHEMA,
+    // 3: operation journal for undo/redo
+    journal::SCHEMA,
+    // 4: accounts and tags
+    "ALTER TABLE entries ADD COLUMN account TEXT; ALTER TABLE entries ADD COLUMN tags TEXT;",
+    // 5: index for date-range queries
+    "CREATE INDEX entries_created_at ON entries(created_at);",
+    // 6: key/value settings (fiscal year, month start day, ...)
+    settings::SCHEMA,
+    // 7: recurring income/expense rules for forecasting
+    recurring::SCHEMA,
+    // 8: references of imported rows, to skip them on re-import
+    imports::SCHEMA,
+];
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // `init_db` has created `entries` by now; a ledger from before the first
+    // migration (user_version 0) still holds data worth keeping.
+    if (version as usize) < MIGRATIONS.len() {
+        let has_entries: bool = conn.query_row("SELECT EXISTS (SELECT 1 FROM entries)", [], |row| row.get(0))?;
+        if has_entries {
+            backup::auto_backup(conn, &format!("pre-migration-v{}", version))?;
+        }
+    }
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        // A failed step rolls back when `tx` is dropped, leaving the database
+        // at the last completed version.
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+        tx.commit()?;
+    }
+    Ok(())
 }
 
 pub fn add_entry(
@@ -52,30 +175,114 @@ pub fn add_entry(
     amount: i64,
     category: &str,
     note: Option<&str>,
-) -> rusqlite::Result<()> {
-    conn.execute(
-        "INSERT INTO entries (kind, amount, category, note) VALUES (?1, ?2, ?3, ?4)",
-        params![kind.to_i64(), amount, category, note],
-    )?;
-    Ok(())
+) -> rusqlite::Result<i64> {
+    let mut e = NewEntry::new(kind, amount, category);
+    e.note = note.map(|n| n.to_string());
+    insert_entry(conn, &e)
+}
+
+pub fn insert_entry(conn: &Connection, e: &NewEntry) -> rusqlite::Result<i64> {
+    run_operation(conn, &format!("add {} {}", e.category, e.amount), |tx| {
+        tx.execute(
+            r#"
+            INSERT INTO entries (kind, amount, category, note, account, tags, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, coalesce(?7, datetime('now','localtime')))
+            "#,
+            params![
+                e.kind.to_i64(),
+                e.amount,
+                e.category,
+                e.note,
+                e.account,
+                filter::join_tags(&e.tags),
+                e.created_at.map(dates::format_timestamp)
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        let after = audit::snapshot(tx, id)?;
+        audit::record(tx, id, "insert", None, after.as_deref())?;
+        Ok(id)
+    })
+}
+
+pub fn update_entry(
+    conn: &Connection,
+    id: i64,
+    kind: Kind,
+    amount: i64,
+    category: &str,
+    note: Option<&str>,
+) -> rusqlite::Result<usize> {
+    run_operation(conn, &format!("edit entry {}", id), |tx| {
+        let before = audit::snapshot(tx, id)?;
+        let n = tx.execute(
+            "UPDATE entries SET kind = ?2, amount = ?3, category = ?4, note = ?5 WHERE id = ?1 AND deleted_at IS NULL",
+            params![id, kind.to_i64(), amount, category, note],
+        )?;
+        if n > 0 {
+            let after = audit::snapshot(tx, id)?;
+            audit::record(tx, id, "update", before.as_deref(), after.as_deref())?;
+        }
+        Ok(n)
+    })
 }
 
 pub fn list_entries(conn: &Connection) -> rusqlite::Result<Vec<Entry>> {
-    let mut stmt = conn.prepare(
+    query_entries(conn, &EntryFilter::new())
+}
+
+/// Moves an entry to the trash. Trashed entries are hidden from every list
+/// and report until they are restored or purged.
+pub fn delete_entry(conn: &Connection, id: i64) -> rusqlite::Result<usize> {
+    run_operation(conn, &format!("delete entry {}", id), |tx| {
+        let before = audit::snapshot(tx, id)?;
+        let n = tx.execute(
+            "UPDATE entries SET deleted_at = datetime('now','localtime') WHERE id = ?1 AND deleted_at IS NULL",
+            params![id],
+        )?;
+        if n > 0 {
+            let after = audit::snapshot(tx, id)?;
+            audit::record(tx, id, "delete", before.as_deref(), after.as_deref())?;
+        }
+        Ok(n)
+    })
+}
+
+pub fn restore_entry(conn: &Connection, id: i64) -> rusqlite::Result<usize> {
+    run_operation(conn, &format!("restore entry {}", id), |tx| {
+        let before = audit::snapshot(tx, id)?;
+        let n = tx.execute(
+            "UPDATE entries SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id],
+        )?;
+        if n > 0 {
+            let after = audit::snapshot(tx, id)?;
+            audit::record(tx, id, "restore", before.as_deref(), after.as_deref())?;
+        }
+        Ok(n)
+    })
+}
+
+#[derive(Debug)]
+pub struct TrashedEntry {
+    pub entry: Entry,
+    pub deleted_at: String,
+}
+
+pub fn list_trash(conn: &Connection) -> rusqlite::Result<Vec<TrashedEntry>> {
+    let mut stmt = conn.prepare(&format!(
         r#"
-        SELECT id, kind, amount, category, note, created_at
+        SELECT {}, deleted_at
         FROM entries
-        ORDER BY datetime(created_at) DESC
+        WHERE deleted_at IS NOT NULL
+        ORDER BY datetime(deleted_at) DESC, id DESC
         "#,
-    )?;
+        filter::ENTRY_SELECT
+    ))?;
     let rows = stmt.query_map([], |row| {
-        Ok(Entry {
-            id: row.get(0)?,
-            kind: Kind::from_i64(row.get::<_, i64>(1)?),
-            amount: row.get(2)?,
-            category: row.get(3)?,
-            note: row.get::<_, Option<String>>(4)?,
-            created_at: row.get(5)?,
+        Ok(TrashedEntry {
+            entry: filter::map_entry(row)?,
+            deleted_at: row.get(8)?,
         })
     })?;
 
@@ -86,36 +293,70 @@ pub fn list_entries(conn: &Connection) -> rusqlite::Result<Vec<Entry>> {
     Ok(v)
 }
 
-pub fn delete_entry(conn: &Connection, id: i64) -> rusqlite::Result<usize> {
-    conn.execute("DELETE FROM entries WHERE id = ?1", params![id])
+/// Permanently removes trashed entries deleted at least `older_than_days` days ago.
+/// `0` empties the whole trash.
+pub fn purge_trash(conn: &Connection, older_than_days: i64) -> rusqlite::Result<usize> {
+    run_operation(conn, "purge trash", |tx| {
+        let cutoff: String = tx.query_row(
+            "SELECT datetime('now','localtime', ?1)",
+            params![format!("-{} days", older_than_days)],
+            |row| row.get(0),
+        )?;
+        let ids = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM entries WHERE deleted_at IS NOT NULL AND datetime(deleted_at) <= ?1",
+            )?;
+            stmt.query_map(params![cutoff], |row| row.get::<_, i64>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        for &id in &ids {
+            let before = audit::snapshot(tx, id)?;
+            tx.execute("DELETE FROM entries WHERE id = ?1", params![id])?;
+            audit::record(tx, id, "purge", before.as_deref(), None)?;
+        }
+        Ok(ids.len())
+    })
 }
 
 pub struct MonthSummary {
-    pub month: String,
+    pub month: YearMonth,
     pub expense: i64,
     pub income: i64,
     pub balance: i64,
 }
 
-pub fn month_summary(conn: &Connection, ym: &str) -> rusqlite::Result<MonthSummary> {
-    let mut stmt = conn.prepare(
+// Income and expense totals for `from <= created_at < until`.
+fn totals_between(conn: &Connection, from: NaiveDate, until: NaiveDate) -> rusqlite::Result<(i64, i64)> {
+    let mut stmt = conn.prepare_cached(
         r#"
         SELECT
             SUM(CASE WHEN kind = 1 THEN amount ELSE 0 END) AS income,
             SUM(CASE WHEN kind = 0 THEN amount ELSE 0 END) AS expense
         FROM entries
-        WHERE substr(created_at, 1, 7) = ?1
+        WHERE deleted_at IS NULL AND created_at >= ?1 AND created_at < ?2
         "#,
     )?;
 
-    let (income_opt, expense_opt): (Option<i64>, Option<i64>) =
-        stmt.query_row(params![ym], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let (income_opt, expense_opt): (Option<i64>, Option<i64>) = stmt.query_row(
+        params![dates::day_start(from), dates::day_start(until)],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
 
-    let income = income_opt.unwrap_or(0);
-    let expense = expense_opt.unwrap_or(0);
+    Ok((income_opt.unwrap_or(0), expense_opt.unwrap_or(0)))
+}
+
+// First day of `start` and first day after `end`, honouring the month start day.
+fn month_bounds(conn: &Connection, start: YearMonth, end: YearMonth) -> rusqlite::Result<(NaiveDate, NaiveDate)> {
+    let settings = load_period_settings(conn)?;
+    Ok((settings.month_start(start), settings.month_start(end.succ())))
+}
+
+pub fn month_summary(conn: &Connection, ym: YearMonth) -> rusqlite::Result<MonthSummary> {
+    let (from, until) = month_bounds(conn, ym, ym)?;
+    let (income, expense) = totals_between(conn, from, until)?;
 
     Ok(MonthSummary {
-        month: ym.to_string(),
+        month: ym,
         income,
         expense,
         balance: income - expense,
@@ -128,27 +369,31 @@ pub struct CategoryTotal {
     pub total: i64,
 }
 
-pub fn category_totals_by_kind(
+pub(crate) fn category_totals_between(
     conn: &Connection,
-    ym: &str,
-    kind: Kind, // Kind::Expense or Kind::Income
+    from: NaiveDate,
+    until: NaiveDate,
+    kind: Kind,
 ) -> rusqlite::Result<Vec<CategoryTotal>> {
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare_cached(
         r#"
         SELECT category, SUM(amount) AS total
         FROM entries
-        WHERE kind = ?2 AND substr(created_at, 1, 7) = ?1
+        WHERE deleted_at IS NULL AND kind = ?3 AND created_at >= ?1 AND created_at < ?2
         GROUP BY category
         ORDER BY total DESC, category ASC
         "#,
     )?;
 
-    let rows = stmt.query_map(params![ym, kind.to_i64()], |row| {
-        Ok(CategoryTotal {
-            category: row.get(0)?,
-            total: row.get(1)?,
-        })
-    })?;
+    let rows = stmt.query_map(
+        params![dates::day_start(from), dates::day_start(until), kind.to_i64()],
+        |row| {
+            Ok(CategoryTotal {
+                category: row.get(0)?,
+                total: row.get(1)?,
+            })
+        },
+    )?;
 
     let mut v = Vec::new();
     for r in rows {
@@ -157,34 +402,30 @@ pub fn category_totals_by_kind(
     Ok(v)
 }
 
+pub fn category_totals_by_kind(
+    conn: &Connection,
+    ym: YearMonth,
+    kind: Kind, // Kind::Expense or Kind::Income
+) -> rusqlite::Result<Vec<CategoryTotal>> {
+    let (from, until) = month_bounds(conn, ym, ym)?;
+    category_totals_between(conn, from, until, kind)
+}
+
 pub struct PeriodSummary {
-    pub start_month: String,
-    pub end_month: String,
+    pub start_month: YearMonth,
+    pub end_month: YearMonth,
     pub income: i64,
     pub expense: i64,
     pub balance: i64,
 }
 
-pub fn summary_in_range(conn: &Connection, start_ym: &str, end_ym: &str) -> rusqlite::Result<PeriodSummary> {
-    let mut stmt = conn.prepare(
-        r#"
-        SELECT
-            SUM(CASE WHEN kind = 1 THEN amount ELSE 0 END) AS income,
-            SUM(CASE WHEN kind = 0 THEN amount ELSE 0 END) AS expense
-        FROM entries
-        WHERE substr(created_at, 1, 7) BETWEEN ?1 AND ?2
-        "#,
-    )?;
-
-    let (income_opt, expense_opt): (Option<i64>, Option<i64>) =
-        stmt.query_row(params![start_ym, end_ym], |row| Ok((row.get(0)?, row.get(1)?)))?;
-
-    let income = income_opt.unwrap_or(0);
-    let expense = expense_opt.unwrap_or(0);
+pub fn summary_in_range(conn: &Connection, start: YearMonth, end: YearMonth) -> rusqlite::Result<PeriodSummary> {
+    let (from, until) = month_bounds(conn, start, end)?;
+    let (income, expense) = totals_between(conn, from, until)?;
 
     Ok(PeriodSummary {
-        start_month: start_ym.to_string(),
-        end_month: end_ym.to_string(),
+        start_month: start,
+        end_month: end,
         income,
         expense,
         balance: income - expense,
@@ -193,78 +434,18 @@ pub fn summary_in_range(conn: &Connection, start_ym: &str, end_ym: &str) -> rusq
 
 pub fn category_totals_by_kind_in_range(
     conn: &Connection,
-    start_ym: &str,
-    end_ym: &str,
+    start: YearMonth,
+    end: YearMonth,
     kind: Kind, // Kind::Expense or Kind::Income
 ) -> rusqlite::Result<Vec<CategoryTotal>> {
-    let mut stmt = conn.prepare(
-        r#"
-        SELECT category, SUM(amount) AS total
-        FROM entries
-        WHERE kind = ?3 AND substr(created_at, 1, 7) BETWEEN ?1 AND ?2
-        GROUP BY category
-        ORDER BY total DESC, category ASC
-        "#,
-    )?;
-
-    let rows = stmt.query_map(params![start_ym, end_ym, kind.to_i64()], |row| {
-        Ok(CategoryTotal {
-            category: row.get(0)?,
-            total: row.get(1)?,
-        })
-    })?;
-
-    let mut v = Vec::new();
-    for r in rows {
-        v.push(r?);
-    }
-    Ok(v)
+    let (from, until) = month_bounds(conn, start, end)?;
+    category_totals_between(conn, from, until, kind)
 }
 
-pub fn entries_in_month(conn: &Connection, ym: &str) -> rusqlite::Result<Vec<Entry>> {
-    let mut stmt = conn.prepare(
-        r#"
-        SELECT id, kind, amount, category, note, created_at
-        FROM entries
-        WHERE substr(created_at, 1, 7) = ?1
-        ORDER BY datetime(created_at) ASC, id ASC
-        "#
-    )?;
-    let rows = stmt.query_map(params![ym], |row| {
-        Ok(Entry {
-            id: row.get(0)?,
-            kind: Kind::from_i64(row.get::<_, i64>(1)?),
-            amount: row.get(2)?,
-            category: row.get(3)?,
-            note: row.get::<_, Option<String>>(4)?,
-            created_at: row.get(5)?,
-        })
-    })?;
-    let mut v = Vec::new();
-    for r in rows { v.push(r?); }
-    Ok(v)
+pub fn entries_in_month(conn: &Connection, ym: YearMonth) -> rusqlite::Result<Vec<Entry>> {
+    query_entries(conn, &EntryFilter::new().month(ym).order(SortOrder::DateAsc))
 }
 
-pub fn entries_in_range(conn: &Connection, start_ym: &str, end_ym: &str) -> rusqlite::Result<Vec<Entry>> {
-    let mut stmt = conn.prepare(
-        r#"
-        SELECT id, kind, amount, category, note, created_at
-        FROM entries
-        WHERE substr(created_at, 1, 7) BETWEEN ?1 AND ?2
-        ORDER BY datetime(created_at) ASC, id ASC
-        "#
-    )?;
-    let rows = stmt.query_map(params![start_ym, end_ym], |row| {
-        Ok(Entry {
-            id: row.get(0)?,
-            kind: Kind::from_i64(row.get::<_, i64>(1)?),
-            amount: row.get(2)?,
-            category: row.get(3)?,
-            note: row.get::<_, Option<String>>(4)?,
-            created_at: row.get(5)?,
-        })
-    })?;
-    let mut v = Vec::new();
-    for r in rows { v.push(r?); }
-    Ok(v)
+pub fn entries_in_range(conn: &Connection, start: YearMonth, end: YearMonth) -> rusqlite::Result<Vec<Entry>> {
+    query_entries(conn, &EntryFilter::new().months(start, end).order(SortOrder::DateAsc))
 }
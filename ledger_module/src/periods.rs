@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, NaiveDate};
+use rusqlite::{Connection, params};
+
+use crate::dates;
+use crate::settings::{PeriodSettings, load_period_settings};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Granularity {
+    Day,
+    /// ISO 8601 week, Monday to Sunday.
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" | "daily" => Ok(Granularity::Day),
+            "week" | "weekly" => Ok(Granularity::Week),
+            "month" | "monthly" => Ok(Granularity::Month),
+            "quarter" | "quarterly" => Ok(Granularity::Quarter),
+            "year" | "yearly" => Ok(Granularity::Year),
+            _ => Err(format!("invalid period: {} (expected day, week, month, quarter or year)", s)),
+        }
+    }
+}
+
+// Months, quarters and years follow the ledger's `PeriodSettings`
+// (month start day, fiscal year start); days and ISO weeks are fixed.
+impl Granularity {
+    /// First day of the bucket containing `d`.
+    fn bucket_start(self, s: &PeriodSettings, d: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => d,
+            Granularity::Week => d - Days::new(d.weekday().num_days_from_monday() as u64),
+            Granularity::Month => s.month_start(s.month_of(d)),
+            Granularity::Quarter => s.month_start(s.quarter_start(s.month_of(d))),
+            Granularity::Year => {
+                let (first, _) = s.fiscal_year_months(s.fiscal_year_of(s.month_of(d)));
+                s.month_start(first)
+            }
+        }
+    }
+
+    /// First day of the bucket after the one starting at `start`.
+    fn next_start(self, s: &PeriodSettings, start: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => start + Days::new(1),
+            Granularity::Week => start + Days::new(7),
+            Granularity::Month => s.month_start(s.month_of(start).succ()),
+            Granularity::Quarter => s.month_start(s.month_of(start).add_months(3)),
+            Granularity::Year => s.month_start(s.month_of(start).add_months(12)),
+        }
+    }
+
+    fn label(self, s: &PeriodSettings, start: NaiveDate) -> String {
+        let fy_prefix = if s.is_calendar_year() { "" } else { "FY" };
+        match self {
+            Granularity::Day => start.format("%Y-%m-%d").to_string(),
+            Granularity::Week => {
+                let w = start.iso_week();
+                format!("{}-W{:02}", w.year(), w.week())
+            }
+            Granularity::Month => s.month_of(start).to_string(),
+            Granularity::Quarter => {
+                let ym = s.month_of(start);
+                format!("{}{}-Q{}", fy_prefix, s.fiscal_year_of(ym), s.quarter_of(ym))
+            }
+            Granularity::Year => format!("{}{}", fy_prefix, s.fiscal_year_of(s.month_of(start))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PeriodTotal {
+    pub label: String,
+    /// First day of the bucket, clipped to the requested range.
+    pub start: NaiveDate,
+    /// Last day of the bucket (inclusive), clipped to the requested range.
+    pub end: NaiveDate,
+    pub income: i64,
+    pub expense: i64,
+    pub balance: i64,
+}
+
+/// Income, expense and balance for every `granularity` bucket touching
+/// `from..=to`, oldest first. Buckets without entries are included with zeros.
+pub fn period_summaries(
+    conn: &Connection,
+    from: NaiveDate,
+    to: NaiveDate,
+    granularity: Granularity,
+) -> rusqlite::Result<Vec<PeriodTotal>> {
+    let settings = load_period_settings(conn)?;
+    let daily = daily_totals(conn, from, to)?;
+
+    let mut out = Vec::new();
+    let mut start = granularity.bucket_start(&settings, from);
+    while start <= to {
+        let next = granularity.next_start(&settings, start);
+        let lo = start.max(from);
+        let hi = next.pred_opt().expect("not the first representable day").min(to);
+        let (mut income, mut expense) = (0, 0);
+        for (_, (i, e)) in daily.range(lo..=hi) {
+            income += i;
+            expense += e;
+        }
+        out.push(PeriodTotal {
+            label: granularity.label(&settings, start),
+            start: lo,
+            end: hi,
+            income,
+            expense,
+            balance: income - expense,
+        });
+        start = next;
+    }
+    Ok(out)
+}
+
+// (income, expense) per calendar day in `from..=to`.
+fn daily_totals(
+    conn: &Connection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> rusqlite::Result<BTreeMap<NaiveDate, (i64, i64)>> {
+    let until = to.succ_opt().expect("not the last representable day");
+    let mut stmt = conn.prepare_cached(
+        r#"
+        SELECT
+            substr(created_at, 1, 10) AS day,
+            SUM(CASE WHEN kind = 1 THEN amount ELSE 0 END) AS income,
+            SUM(CASE WHEN kind = 0 THEN amount ELSE 0 END) AS expense
+        FROM entries
+        WHERE deleted_at IS NULL AND created_at >= ?1 AND created_at < ?2
+        GROUP BY day
+        "#,
+    )?;
+    let rows = stmt.query_map(params![dates::day_start(from), dates::day_start(until)], |row| {
+        Ok((row.get::<_, NaiveDate>(0)?, (row.get(1)?, row.get(2)?)))
+    })?;
+
+    let mut m = BTreeMap::new();
+    for r in rows {
+        let (day, totals) = r?;
+        m.insert(day, totals);
+    }
+    Ok(m)
+}
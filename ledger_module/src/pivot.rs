@@ -0,0 +1,169 @@
+use rusqlite::Connection;
+
+use crate::settings::load_period_settings;
+use crate::{Kind, YearMonth, category_totals_between};
+
+#[derive(Debug)]
+pub struct PivotRow {
+    pub category: String,
+    /// One value per month of `PivotTable::months`.
+    pub values: Vec<i64>,
+    pub total: i64,
+    /// Average per month over the whole range, empty months included.
+    pub average: f64,
+}
+
+/// Category × month totals for one kind.
+#[derive(Debug)]
+pub struct PivotTable {
+    pub kind: Kind,
+    pub months: Vec<YearMonth>,
+    /// Sorted by total, largest first.
+    pub rows: Vec<PivotRow>,
+    /// Sum of every category, one value per month.
+    pub month_totals: Vec<i64>,
+    pub grand_total: i64,
+    /// Average of `month_totals`.
+    pub month_average: f64,
+}
+
+pub fn category_month_pivot(
+    conn: &Connection,
+    start: YearMonth,
+    end: YearMonth,
+    kind: Kind,
+) -> rusqlite::Result<PivotTable> {
+    let settings = load_period_settings(conn)?;
+    let months = start.through(end);
+    let n = months.len();
+
+    let mut rows: Vec<PivotRow> = Vec::new();
+    for (i, ym) in months.iter().enumerate() {
+        let totals = category_totals_between(
+            conn,
+            settings.month_start(*ym),
+            settings.month_start(ym.succ()),
+            kind,
+        )?;
+        for t in totals {
+            let row = match rows.iter().position(|r| r.category == t.category) {
+                Some(p) => &mut rows[p],
+                None => {
+                    rows.push(PivotRow {
+                        category: t.category,
+                        values: vec![0; n],
+                        total: 0,
+                        average: 0.0,
+                    });
+                    rows.last_mut().expect("just pushed")
+                }
+            };
+            row.values[i] = t.total;
+        }
+    }
+
+    let mut month_totals = vec![0; n];
+    for r in &mut rows {
+        r.total = r.values.iter().sum();
+        r.average = if n > 0 { r.total as f64 / n as f64 } else { 0.0 };
+        for (i, v) in r.values.iter().enumerate() {
+            month_totals[i] += v;
+        }
+    }
+    rows.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.category.cmp(&b.category)));
+
+    let grand_total = month_totals.iter().sum();
+    Ok(PivotTable {
+        kind,
+        months,
+        rows,
+        month_totals,
+        grand_total,
+        month_average: if n > 0 { grand_total as f64 / n as f64 } else { 0.0 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::settings::{PeriodSettings, save_period_settings};
+    use crate::{NewEntry, init_db, insert_entry};
+
+    fn add(conn: &Connection, kind: Kind, amount: i64, category: &str, day: &str) {
+        let mut e = NewEntry::new(kind, amount, category);
+        e.created_at = NaiveDateTime::parse_from_str(&format!("{} 12:00:00", day), "%Y-%m-%d %H:%M:%S").ok();
+        insert_entry(conn, &e).unwrap();
+    }
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    fn rows(t: &PivotTable) -> Vec<(&str, Vec<i64>, i64)> {
+        t.rows.iter().map(|r| (r.category.as_str(), r.values.clone(), r.total)).collect()
+    }
+
+    fn ledger() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        add(&conn, Kind::Expense, 80000, "rent", "2025-05-01");
+        add(&conn, Kind::Expense, 26000, "food", "2025-05-10");
+        add(&conn, Kind::Expense, 4000, "food", "2025-05-28");
+        add(&conn, Kind::Income, 300000, "salary", "2025-06-25");
+        add(&conn, Kind::Expense, 12000, "food", "2025-07-03");
+        add(&conn, Kind::Expense, 3000, "books", "2025-07-15");
+        conn
+    }
+
+    #[test]
+    fn totals_per_category_and_month() {
+        let conn = ledger();
+        let t = category_month_pivot(&conn, ym("2025-05"), ym("2025-07"), Kind::Expense).unwrap();
+
+        assert_eq!(t.months, [ym("2025-05"), ym("2025-06"), ym("2025-07")]);
+        // June has no expenses but still gets a column.
+        assert_eq!(
+            rows(&t),
+            [
+                ("rent", vec![80000, 0, 0], 80000),
+                ("food", vec![30000, 0, 12000], 42000),
+                ("books", vec![0, 0, 3000], 3000),
+            ]
+        );
+        let averages: Vec<f64> = t.rows.iter().map(|r| r.average).collect();
+        assert!((averages[0] - 80000.0 / 3.0).abs() < 1e-9);
+        assert_eq!(averages[1..], [14000.0, 1000.0]);
+        assert_eq!(t.month_totals, [110000, 0, 15000]);
+        assert_eq!(t.grand_total, 125000);
+        assert!((t.month_average - 125000.0 / 3.0).abs() < 1e-9);
+
+        let income = category_month_pivot(&conn, ym("2025-05"), ym("2025-07"), Kind::Income).unwrap();
+        assert_eq!(rows(&income), [("salary", vec![0, 300000, 0], 300000)]);
+    }
+
+    #[test]
+    fn months_follow_the_month_start_day() {
+        let conn = ledger();
+        save_period_settings(&conn, &PeriodSettings { month_start_day: 25, ..PeriodSettings::default() }).unwrap();
+        let t = category_month_pivot(&conn, ym("2025-05"), ym("2025-07"), Kind::Expense).unwrap();
+        assert_eq!(
+            rows(&t),
+            [
+                ("rent", vec![80000, 0, 0], 80000),
+                ("food", vec![26000, 4000, 12000], 42000),
+                ("books", vec![0, 0, 3000], 3000),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_empty_range_has_no_rows() {
+        let conn = ledger();
+        let t = category_month_pivot(&conn, ym("2024-01"), ym("2024-02"), Kind::Expense).unwrap();
+        assert!(t.rows.is_empty());
+        assert_eq!(t.month_totals, [0, 0]);
+        assert_eq!((t.grand_total, t.month_average), (0, 0.0));
+    }
+}
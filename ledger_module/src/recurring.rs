@@ -0,0 +1,131 @@
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, params};
+
+use crate::{Kind, YearMonth};
+
+pub(crate) const SCHEMA: &str = r#"
+    CREATE TABLE recurring (
+        id           INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind         INTEGER NOT NULL CHECK(kind IN (0, 1)),
+        amount       INTEGER NOT NULL CHECK(amount > 0),
+        category     TEXT NOT NULL,
+        note         TEXT,
+        day          INTEGER NOT NULL CHECK(day BETWEEN 1 AND 31),
+        every_months INTEGER NOT NULL DEFAULT 1 CHECK(every_months > 0),
+        start_month  TEXT NOT NULL,
+        end_month    TEXT,
+        created_at   TEXT NOT NULL DEFAULT (datetime('now','localtime'))
+    );
+"#;
+
+/// An income or expense expected on a fixed day of the month, such as rent
+/// or a salary. Rules feed the forecast; they never create entries.
+#[derive(Debug, Clone)]
+pub struct RecurringRule {
+    pub id: i64,
+    pub kind: Kind,
+    pub amount: i64,
+    pub category: String,
+    pub note: Option<String>,
+    /// Day of the calendar month; moved to the last day in shorter months.
+    pub day: u32,
+    /// Repeats every `every_months` months counted from `start`.
+    pub every_months: u32,
+    pub start: YearMonth,
+    /// Last month the rule applies to, if it ends.
+    pub end: Option<YearMonth>,
+}
+
+impl RecurringRule {
+    /// Whether the rule falls on `date`.
+    pub fn is_due(&self, date: NaiveDate) -> bool {
+        let ym = YearMonth::of(date);
+        let since = ym.months_since(self.start);
+        since >= 0
+            && since % self.every_months.max(1) as i32 == 0
+            && self.end.is_none_or(|end| ym <= end)
+            && date.day() == self.day.min(ym.last_day().day())
+    }
+}
+
+/// Values for `add_recurring`.
+#[derive(Debug, Clone)]
+pub struct NewRecurring {
+    pub kind: Kind,
+    pub amount: i64,
+    pub category: String,
+    pub note: Option<String>,
+    pub day: u32,
+    pub every_months: u32,
+    pub start: YearMonth,
+    pub end: Option<YearMonth>,
+}
+
+impl NewRecurring {
+    /// A monthly rule on `day`, starting in month `start`.
+    pub fn new(kind: Kind, amount: i64, category: &str, day: u32, start: YearMonth) -> Self {
+        NewRecurring {
+            kind,
+            amount,
+            category: category.to_string(),
+            note: None,
+            day,
+            every_months: 1,
+            start,
+            end: None,
+        }
+    }
+}
+
+pub fn add_recurring(conn: &Connection, r: &NewRecurring) -> rusqlite::Result<i64> {
+    conn.execute(
+        r#"
+        INSERT INTO recurring (kind, amount, category, note, day, every_months, start_month, end_month)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        "#,
+        params![
+            r.kind.to_i64(),
+            r.amount,
+            r.category,
+            r.note,
+            r.day,
+            r.every_months,
+            r.start,
+            r.end
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn list_recurring(conn: &Connection) -> rusqlite::Result<Vec<RecurringRule>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT id, kind, amount, category, note, day, every_months, start_month, end_month
+        FROM recurring
+        ORDER BY day ASC, id ASC
+        "#,
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(RecurringRule {
+            id: row.get(0)?,
+            kind: Kind::from_i64(row.get::<_, i64>(1)?),
+            amount: row.get(2)?,
+            category: row.get(3)?,
+            note: row.get(4)?,
+            day: row.get(5)?,
+            every_months: row.get(6)?,
+            start: row.get(7)?,
+            end: row.get(8)?,
+        })
+    })?;
+
+    let mut v = Vec::new();
+    for r in rows {
+        v.push(r?);
+    }
+    Ok(v)
+}
+
+pub fn remove_recurring(conn: &Connection, id: i64) -> rusqlite::Result<usize> {
+    conn.execute("DELETE FROM recurring WHERE id = ?1", params![id])
+}
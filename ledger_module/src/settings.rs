@@ -0,0 +1,221 @@
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::YearMonth;
+use crate::dates::YEARS;
+
+pub(crate) const SCHEMA: &str = r#"
+    CREATE TABLE settings (
+        key   TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+"#;
+
+pub fn get_setting(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+const FISCAL_YEAR_START_MONTH: &str = "fiscal_year_start_month";
+const MONTH_START_DAY: &str = "month_start_day";
+
+/// How dates are grouped into months, quarters and years.
+///
+/// With `month_start_day = 25`, the month `2025-09` runs from 2025-08-25 to
+/// 2025-09-24: a month is named after the calendar month it ends in. When the
+/// previous month is too short for the start day, it starts on that month's
+/// last day instead.
+///
+/// With `fiscal_year_start_month = 4`, fiscal year 2025 covers the months
+/// 2025-04 to 2026-03 and its first quarter is April to June.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PeriodSettings {
+    pub fiscal_year_start_month: u32,
+    pub month_start_day: u32,
+}
+
+impl Default for PeriodSettings {
+    fn default() -> Self {
+        PeriodSettings {
+            fiscal_year_start_month: 1,
+            month_start_day: 1,
+        }
+    }
+}
+
+impl PeriodSettings {
+    /// First day of month `ym`.
+    pub fn month_start(&self, ym: YearMonth) -> NaiveDate {
+        let day = self.month_start_day.clamp(1, 31);
+        if day == 1 {
+            return ym.first_day();
+        }
+        let prev = ym.pred();
+        let day = day.min(prev.last_day().day());
+        NaiveDate::from_ymd_opt(prev.year(), prev.month(), day).expect("day clamped to month length")
+    }
+
+    /// Last day (inclusive) of month `ym`.
+    pub fn month_end(&self, ym: YearMonth) -> NaiveDate {
+        self.month_start(ym.succ()).pred_opt().expect("not the first representable day")
+    }
+
+    /// The month that `date` falls in.
+    pub fn month_of(&self, date: NaiveDate) -> YearMonth {
+        let ym = YearMonth::of(date);
+        if date >= self.month_start(ym.succ()) { ym.succ() } else { ym }
+    }
+
+    fn fiscal_start_month(&self) -> u32 {
+        self.fiscal_year_start_month.clamp(1, 12)
+    }
+
+    /// Fiscal year containing month `ym`, named after the calendar year it starts in.
+    pub fn fiscal_year_of(&self, ym: YearMonth) -> i32 {
+        if ym.month() >= self.fiscal_start_month() { ym.year() } else { ym.year() - 1 }
+    }
+
+    /// First and last month of fiscal year `year`, which is clamped to
+    /// `dates::YEARS`.
+    pub fn fiscal_year_months(&self, year: i32) -> (YearMonth, YearMonth) {
+        let year = year.clamp(*YEARS.start(), *YEARS.end());
+        let start = YearMonth::new(year, self.fiscal_start_month()).expect("year and month clamped");
+        (start, start.add_months(11))
+    }
+
+    /// Fiscal quarter (1-4) containing month `ym`.
+    pub fn quarter_of(&self, ym: YearMonth) -> u32 {
+        (ym.month() + 12 - self.fiscal_start_month()) % 12 / 3 + 1
+    }
+
+    /// First month of the fiscal quarter containing `ym`.
+    pub fn quarter_start(&self, ym: YearMonth) -> YearMonth {
+        let (fy_start, _) = self.fiscal_year_months(self.fiscal_year_of(ym));
+        fy_start.add_months(3 * (self.quarter_of(ym) as i32 - 1))
+    }
+
+    pub fn is_calendar_year(&self) -> bool {
+        self.fiscal_start_month() == 1
+    }
+}
+
+pub fn load_period_settings(conn: &Connection) -> rusqlite::Result<PeriodSettings> {
+    let d = PeriodSettings::default();
+    let read = |key: &str, default: u32| -> rusqlite::Result<u32> {
+        Ok(get_setting(conn, key)?.and_then(|v| v.parse().ok()).unwrap_or(default))
+    };
+    Ok(PeriodSettings {
+        fiscal_year_start_month: read(FISCAL_YEAR_START_MONTH, d.fiscal_year_start_month)?,
+        month_start_day: read(MONTH_START_DAY, d.month_start_day)?,
+    })
+}
+
+pub fn save_period_settings(conn: &Connection, s: &PeriodSettings) -> rusqlite::Result<()> {
+    set_setting(conn, FISCAL_YEAR_START_MONTH, &s.fiscal_year_start_month.to_string())?;
+    set_setting(conn, MONTH_START_DAY, &s.month_start_day.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn starting_on(day: u32) -> PeriodSettings {
+        PeriodSettings { month_start_day: day, ..PeriodSettings::default() }
+    }
+
+    fn fiscal_from(month: u32) -> PeriodSettings {
+        PeriodSettings { fiscal_year_start_month: month, ..PeriodSettings::default() }
+    }
+
+    #[test]
+    fn late_start_days_clamp_to_the_end_of_february() {
+        for day in [29, 30, 31] {
+            let s = starting_on(day);
+            // March starts in February: on the 29th in a leap year, the 28th otherwise.
+            assert_eq!(s.month_start(ym("2024-03")), date("2024-02-29"), "day {}", day);
+            assert_eq!(s.month_start(ym("2025-03")), date("2025-02-28"), "day {}", day);
+            assert_eq!(s.month_end(ym("2024-02")), date("2024-02-28"), "day {}", day);
+            assert_eq!(s.month_end(ym("2025-02")), date("2025-02-27"), "day {}", day);
+            assert_eq!(s.month_of(date("2025-02-28")), ym("2025-03"), "day {}", day);
+            assert_eq!(s.month_of(date("2024-02-29")), ym("2024-03"), "day {}", day);
+        }
+        assert_eq!(starting_on(29).month_start(ym("2024-02")), date("2024-01-29"));
+        assert_eq!(starting_on(31).month_start(ym("2024-05")), date("2024-04-30"));
+    }
+
+    #[test]
+    fn months_wrap_from_december_into_january() {
+        let s = starting_on(25);
+        assert_eq!(s.month_start(ym("2026-01")), date("2025-12-25"));
+        assert_eq!(s.month_end(ym("2025-12")), date("2025-12-24"));
+        assert_eq!(s.month_of(date("2025-12-24")), ym("2025-12"));
+        assert_eq!(s.month_of(date("2025-12-25")), ym("2026-01"));
+        assert_eq!(s.month_of(date("2026-01-24")), ym("2026-01"));
+        assert_eq!(s.month_of(date("2026-01-25")), ym("2026-02"));
+    }
+
+    #[test]
+    fn months_cover_every_day_exactly_once() {
+        for day in 1..=31 {
+            let s = starting_on(day);
+            let mut d = date("2023-11-01");
+            while d <= date("2025-03-31") {
+                let m = s.month_of(d);
+                assert!(s.month_start(m) <= d && d <= s.month_end(m), "{} with start day {}", d, day);
+                assert_eq!(s.month_end(m).succ_opt().unwrap(), s.month_start(m.succ()));
+                d = d.succ_opt().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn april_fiscal_year_spans_the_calendar_year() {
+        let s = fiscal_from(4);
+        assert_eq!(s.fiscal_year_of(ym("2025-04")), 2025);
+        assert_eq!(s.fiscal_year_of(ym("2025-12")), 2025);
+        assert_eq!(s.fiscal_year_of(ym("2026-01")), 2025);
+        assert_eq!(s.fiscal_year_of(ym("2026-03")), 2025);
+        assert_eq!(s.fiscal_year_of(ym("2026-04")), 2026);
+        assert_eq!(s.fiscal_year_months(2025), (ym("2025-04"), ym("2026-03")));
+    }
+
+    #[test]
+    fn quarters_change_at_fiscal_quarter_edges() {
+        let s = fiscal_from(4);
+        let edges = [
+            ("2025-04", 1, "2025-04"),
+            ("2025-06", 1, "2025-04"),
+            ("2025-07", 2, "2025-07"),
+            ("2025-09", 2, "2025-07"),
+            ("2025-10", 3, "2025-10"),
+            ("2025-12", 3, "2025-10"),
+            ("2026-01", 4, "2026-01"),
+            ("2026-03", 4, "2026-01"),
+        ];
+        for (month, quarter, start) in edges {
+            assert_eq!(s.quarter_of(ym(month)), quarter, "{}", month);
+            assert_eq!(s.quarter_start(ym(month)), ym(start), "{}", month);
+        }
+        let calendar = PeriodSettings::default();
+        assert_eq!(calendar.quarter_of(ym("2025-01")), 1);
+        assert_eq!(calendar.quarter_of(ym("2025-03")), 1);
+        assert_eq!(calendar.quarter_of(ym("2025-04")), 2);
+        assert_eq!(calendar.quarter_of(ym("2025-12")), 4);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{Connection, params};
+
+use crate::anomalies::median;
+use crate::forecast::first_entry_at;
+use crate::settings::load_period_settings;
+use crate::{Entry, EntryFilter, Kind, SortOrder, YearMonth, category_month_pivot, dates, month_summary, query_entries};
+
+const LARGEST_EXPENSES: i64 = 10;
+const ROLLING_WINDOWS: [i32; 3] = [3, 6, 12];
+
+#[derive(Debug)]
+pub struct MonthStats {
+    pub month: YearMonth,
+    pub income: i64,
+    pub expense: i64,
+    /// Share of income not spent, in percent; `None` without income.
+    pub savings_rate: Option<f64>,
+    /// Average monthly expense over the 3, 6 and 12 months ending with this
+    /// one. Months before the first entry are left out; `None` if that leaves none.
+    pub rolling_expense: [Option<f64>; 3],
+}
+
+#[derive(Debug)]
+pub struct CategoryStats {
+    pub category: String,
+    /// Expense total over the period.
+    pub total: i64,
+    /// Per month over the period, months without entries included.
+    pub average: f64,
+    pub median: f64,
+    /// Latest expense in the category up to the end of the period.
+    pub last_entry: NaiveDate,
+    pub days_since_last: i64,
+}
+
+#[derive(Debug)]
+pub struct Stats {
+    pub start: YearMonth,
+    pub end: YearMonth,
+    pub months: Vec<MonthStats>,
+    /// Savings rate over the whole period.
+    pub savings_rate: Option<f64>,
+    /// Every expense category used up to the end of the period, largest total first.
+    pub categories: Vec<CategoryStats>,
+    /// Largest single expenses of the period, largest first.
+    pub largest: Vec<Entry>,
+}
+
+/// Key statistics for the months `start..=end`. Days since the last entry
+/// are counted up to `as_of`.
+pub fn stats(conn: &Connection, start: YearMonth, end: YearMonth, as_of: NaiveDate) -> rusqlite::Result<Stats> {
+    let settings = load_period_settings(conn)?;
+    let first_month = first_entry_at(conn)?.map(|t| settings.month_of(t.date()));
+
+    // Income and expense per month, reaching back far enough for the longest rolling window.
+    let mut totals_by_month = BTreeMap::new();
+    let lookback = start.add_months(-(ROLLING_WINDOWS[2] - 1));
+    for ym in lookback.through(end) {
+        let s = month_summary(conn, ym)?;
+        totals_by_month.insert(ym, (s.income, s.expense));
+    }
+
+    let mut months = Vec::new();
+    let (mut income_total, mut expense_total) = (0, 0);
+    for ym in start.through(end) {
+        let (income, expense) = totals_by_month[&ym];
+        income_total += income;
+        expense_total += expense;
+        let rolling_expense = ROLLING_WINDOWS.map(|w| {
+            let mut from = ym.add_months(-(w - 1));
+            if let Some(first) = first_month {
+                from = from.max(first);
+            }
+            let window = from.through(ym);
+            if window.is_empty() {
+                return None;
+            }
+            let sum: i64 = window.iter().map(|m| totals_by_month[m].1).sum();
+            Some(sum as f64 / window.len() as f64)
+        });
+        months.push(MonthStats {
+            month: ym,
+            income,
+            expense,
+            savings_rate: savings_rate(income, expense),
+            rolling_expense,
+        });
+    }
+
+    let pivot = category_month_pivot(conn, start, end, Kind::Expense)?;
+    let until = settings.month_start(end.succ());
+    let mut categories = Vec::new();
+    for (category, last) in last_expense_by_category(conn, until)? {
+        let (total, average, median) = match pivot.rows.iter().find(|r| r.category == category) {
+            Some(r) => (r.total, r.average, median(&r.values)),
+            None => (0, 0.0, 0.0),
+        };
+        let last_entry = last.date();
+        categories.push(CategoryStats {
+            category,
+            total,
+            average,
+            median,
+            last_entry,
+            days_since_last: (as_of - last_entry).num_days(),
+        });
+    }
+    categories.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.category.cmp(&b.category)));
+
+    let largest = query_entries(
+        conn,
+        &EntryFilter::new()
+            .months(start, end)
+            .kind(Kind::Expense)
+            .order(SortOrder::AmountDesc)
+            .limit(LARGEST_EXPENSES),
+    )?;
+
+    Ok(Stats {
+        start,
+        end,
+        months,
+        savings_rate: savings_rate(income_total, expense_total),
+        categories,
+        largest,
+    })
+}
+
+fn savings_rate(income: i64, expense: i64) -> Option<f64> {
+    (income > 0).then(|| (income - expense) as f64 * 100.0 / income as f64)
+}
+
+fn last_expense_by_category(conn: &Connection, until: NaiveDate) -> rusqlite::Result<Vec<(String, NaiveDateTime)>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT category, MAX(created_at)
+        FROM entries
+        WHERE deleted_at IS NULL AND kind = 0 AND created_at < ?1
+        GROUP BY category
+        "#,
+    )?;
+    let rows = stmt.query_map(params![dates::day_start(until)], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut v = Vec::new();
+    for r in rows {
+        v.push(r?);
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NewEntry, init_db, insert_entry};
+
+    fn add(conn: &Connection, kind: Kind, amount: i64, category: &str, day: &str) {
+        let mut e = NewEntry::new(kind, amount, category);
+        e.created_at = NaiveDateTime::parse_from_str(&format!("{} 12:00:00", day), "%Y-%m-%d %H:%M:%S").ok();
+        insert_entry(conn, &e).unwrap();
+    }
+
+    fn ym(s: &str) -> YearMonth {
+        s.parse().unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn ledger() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        for (kind, amount, category, day) in [
+            (Kind::Income, 300000, "salary", "2025-04-25"),
+            (Kind::Expense, 100000, "food", "2025-04-05"),
+            (Kind::Expense, 20000, "travel", "2025-04-10"),
+            (Kind::Income, 300000, "salary", "2025-05-25"),
+            (Kind::Expense, 60000, "food", "2025-05-05"),
+            (Kind::Expense, 90000, "rent", "2025-05-25"),
+            (Kind::Expense, 30000, "food", "2025-06-06"),
+            (Kind::Income, 200000, "salary", "2025-07-25"),
+            (Kind::Expense, 50000, "food", "2025-07-07"),
+            (Kind::Expense, 10000, "books", "2025-07-20"),
+            // After the period: not part of any figure.
+            (Kind::Expense, 999, "food", "2025-08-01"),
+        ] {
+            add(&conn, kind, amount, category, day);
+        }
+        conn
+    }
+
+    #[test]
+    fn months_rates_and_rolling_averages() {
+        let conn = ledger();
+        let s = stats(&conn, ym("2025-05"), ym("2025-07"), date("2025-07-31")).unwrap();
+        let months: Vec<(YearMonth, i64, i64, Option<f64>)> =
+            s.months.iter().map(|m| (m.month, m.income, m.expense, m.savings_rate)).collect();
+        assert_eq!(
+            months,
+            [
+                (ym("2025-05"), 300000, 150000, Some(50.0)),
+                (ym("2025-06"), 0, 30000, None),
+                (ym("2025-07"), 200000, 60000, Some(70.0)),
+            ]
+        );
+        // Rolling windows start at the first entry (April).
+        let rolling: Vec<[Option<f64>; 3]> = s.months.iter().map(|m| m.rolling_expense).collect();
+        assert_eq!(
+            rolling,
+            [
+                [Some(135000.0); 3],
+                [Some(100000.0); 3],
+                [Some(80000.0), Some(90000.0), Some(90000.0)],
+            ]
+        );
+        assert_eq!(s.savings_rate, Some(52.0));
+    }
+
+    #[test]
+    fn categories_and_largest_expenses() {
+        let conn = ledger();
+        let s = stats(&conn, ym("2025-05"), ym("2025-07"), date("2025-07-31")).unwrap();
+        let categories: Vec<(&str, i64, f64, NaiveDate, i64)> = s
+            .categories
+            .iter()
+            .map(|c| (c.category.as_str(), c.total, c.median, c.last_entry, c.days_since_last))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                ("food", 140000, 50000.0, date("2025-07-07"), 24),
+                ("rent", 90000, 0.0, date("2025-05-25"), 67),
+                ("books", 10000, 0.0, date("2025-07-20"), 11),
+                // Not used in the period, but still listed with its last use.
+                ("travel", 0, 0.0, date("2025-04-10"), 112),
+            ]
+        );
+        assert!((s.categories[0].average - 140000.0 / 3.0).abs() < 1e-9);
+
+        let largest: Vec<i64> = s.largest.iter().map(|e| e.amount).collect();
+        assert_eq!(largest, [90000, 60000, 50000, 30000, 10000]);
+    }
+
+    #[test]
+    fn months_before_the_first_entry_have_no_figures() {
+        let conn = ledger();
+        let s = stats(&conn, ym("2025-01"), ym("2025-02"), date("2025-02-28")).unwrap();
+        assert!(s.months.iter().all(|m| m.expense == 0 && m.savings_rate.is_none() && m.rolling_expense == [None; 3]));
+        assert_eq!(s.savings_rate, None);
+        assert!(s.categories.is_empty() && s.largest.is_empty());
+    }
+}
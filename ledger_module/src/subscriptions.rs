@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use rusqlite::Connection;
+
+use crate::recurring::{NewRecurring, list_recurring};
+use crate::{Entry, EntryFilter, Kind, SortOrder, YearMonth, query_entries};
+
+// Amounts within this ratio of the smallest one count as the same charge,
+// which allows for small price changes.
+const AMOUNT_TOLERANCE: f64 = 1.15;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interval {
+    Monthly,
+    Yearly,
+}
+
+impl Interval {
+    fn months(self) -> u32 {
+        match self {
+            Interval::Monthly => 1,
+            Interval::Yearly => 12,
+        }
+    }
+
+    fn per_year(self) -> i64 {
+        12 / self.months() as i64
+    }
+
+    // Accepted gaps in days between two charges, and the fewest charges that
+    // make a series.
+    fn rule(self) -> (std::ops::RangeInclusive<i64>, usize) {
+        match self {
+            Interval::Monthly => (24..=38, 3),
+            Interval::Yearly => (350..=380, 2),
+        }
+    }
+}
+
+/// Expenses that repeat with a similar amount and note at a regular interval.
+#[derive(Debug)]
+pub struct Subscription {
+    pub category: String,
+    /// Note of the latest charge.
+    pub note: Option<String>,
+    pub interval: Interval,
+    /// Amount of the latest charge.
+    pub amount: i64,
+    pub annual_cost: i64,
+    pub first_seen: NaiveDate,
+    pub last_seen: NaiveDate,
+    /// Ids of the charges in the series, oldest first.
+    pub entry_ids: Vec<i64>,
+    /// Whether a recurring rule with the same category and amount already exists.
+    pub tracked: bool,
+}
+
+impl Subscription {
+    /// A recurring rule continuing the series on the day of its latest charge.
+    pub fn to_recurring(&self) -> NewRecurring {
+        let mut r = NewRecurring::new(
+            Kind::Expense,
+            self.amount,
+            &self.category,
+            self.last_seen.day(),
+            YearMonth::of(self.last_seen),
+        );
+        r.note = self.note.clone();
+        r.every_months = self.interval.months();
+        r
+    }
+}
+
+/// Finds repeating charges among all expenses, largest annual cost first.
+///
+/// Charges are grouped by category and note (ignoring case, digits and
+/// punctuation, so "Netflix 08/2025" matches "netflix 09/2025"), then by
+/// amount. A group is a subscription when the gaps between consecutive
+/// charges are mostly about a month (at least 3 charges) or about a year
+/// (at least 2).
+pub fn detect_subscriptions(conn: &Connection) -> rusqlite::Result<Vec<Subscription>> {
+    let entries = query_entries(conn, &EntryFilter::new().kind(Kind::Expense).order(SortOrder::AmountAsc))?;
+    let rules = list_recurring(conn)?;
+
+    let mut groups: BTreeMap<(String, String), Vec<Entry>> = BTreeMap::new();
+    for e in entries {
+        let key = (e.category.clone(), normalize_note(e.note.as_deref()));
+        groups.entry(key).or_default().push(e);
+    }
+
+    let mut out = Vec::new();
+    for (_, group) in groups {
+        // Entries arrive sorted by amount; split where the amount jumps.
+        let mut clusters: Vec<Vec<Entry>> = Vec::new();
+        for e in group {
+            match clusters.last_mut() {
+                Some(c) if e.amount as f64 <= c[0].amount as f64 * AMOUNT_TOLERANCE => c.push(e),
+                _ => clusters.push(vec![e]),
+            }
+        }
+        for mut series in clusters {
+            series.sort_by_key(|e| (e.created_at, e.id));
+            let Some(interval) = classify(&series) else {
+                continue;
+            };
+            let first = &series[0];
+            let last = &series[series.len() - 1];
+            out.push(Subscription {
+                category: last.category.clone(),
+                note: last.note.clone(),
+                interval,
+                amount: last.amount,
+                annual_cost: last.amount * interval.per_year(),
+                first_seen: first.created_at.date(),
+                last_seen: last.created_at.date(),
+                entry_ids: series.iter().map(|e| e.id).collect(),
+                tracked: rules.iter().any(|r| {
+                    r.kind == Kind::Expense && r.category == last.category && r.amount == last.amount
+                }),
+            });
+        }
+    }
+    out.sort_by(|a, b| b.annual_cost.cmp(&a.annual_cost).then_with(|| a.category.cmp(&b.category)));
+    Ok(out)
+}
+
+// Three out of four gaps must fit, so one skipped month or one double charge
+// does not hide a series.
+fn classify(series: &[Entry]) -> Option<Interval> {
+    let gaps: Vec<i64> = series
+        .windows(2)
+        .map(|w| (w[1].created_at.date() - w[0].created_at.date()).num_days())
+        .collect();
+    [Interval::Monthly, Interval::Yearly].into_iter().find(|i| {
+        let (gap, min_count) = i.rule();
+        let fitting = gaps.iter().filter(|g| gap.contains(g)).count();
+        series.len() >= min_count && fitting * 4 >= gaps.len() * 3
+    })
+}
+
+fn normalize_note(note: Option<&str>) -> String {
+    let cleaned: String = note
+        .unwrap_or("")
+        .chars()
+        .map(|c| if c.is_alphabetic() { c.to_lowercase().next().unwrap_or(c) } else { ' ' })
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::{NewEntry, add_recurring, init_db, insert_entry};
+
+    fn spend(conn: &Connection, amount: i64, category: &str, note: &str, day: &str) {
+        let mut e = NewEntry::new(Kind::Expense, amount, category);
+        e.note = (!note.is_empty()).then(|| note.to_string());
+        e.created_at = NaiveDateTime::parse_from_str(&format!("{} 12:00:00", day), "%Y-%m-%d %H:%M:%S").ok();
+        insert_entry(conn, &e).unwrap();
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn monthly_and_yearly_series_are_detected() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        // Monthly, with a price rise in May and one charge a few days late.
+        for (day, amount) in [("01-15", 1490), ("02-14", 1490), ("03-18", 1490), ("04-15", 1490), ("05-15", 1590), ("06-15", 1590)] {
+            spend(&conn, amount, "entertainment", &format!("Netflix 2025/{}", &day[..2]), &format!("2025-{}", day));
+        }
+        // Yearly, already tracked by a recurring rule.
+        spend(&conn, 1500, "web", "example.com renewal", "2024-03-01");
+        spend(&conn, 1500, "web", "example.com renewal", "2025-03-03");
+        add_recurring(&conn, &NewRecurring::new(Kind::Expense, 1500, "web", 3, "2026-03".parse().unwrap())).unwrap();
+        // Not subscriptions: irregular shopping, two charges only, and a
+        // charge of a very different amount with the same note.
+        for (day, amount) in [("01-03", 3000), ("01-09", 3200), ("01-12", 3100), ("01-20", 3300), ("02-02", 3050)] {
+            spend(&conn, amount, "food", "", &format!("2025-{}", day));
+        }
+        spend(&conn, 8000, "fitness", "gym", "2025-04-01");
+        spend(&conn, 8000, "fitness", "gym", "2025-05-01");
+        spend(&conn, 9900, "entertainment", "NETFLIX 2025/02", "2025-02-20");
+
+        let subs = detect_subscriptions(&conn).unwrap();
+        let found: Vec<(&str, Interval, i64, i64, bool)> =
+            subs.iter().map(|s| (s.category.as_str(), s.interval, s.amount, s.annual_cost, s.tracked)).collect();
+        assert_eq!(
+            found,
+            [
+                ("entertainment", Interval::Monthly, 1590, 1590 * 12, false),
+                ("web", Interval::Yearly, 1500, 1500, true),
+            ]
+        );
+        let netflix = &subs[0];
+        assert_eq!(netflix.entry_ids, [1, 2, 3, 4, 5, 6]);
+        assert_eq!((netflix.first_seen, netflix.last_seen), (date("2025-01-15"), date("2025-06-15")));
+
+        let rule = netflix.to_recurring();
+        assert_eq!((rule.amount, rule.day, rule.every_months), (1590, 15, 1));
+        assert_eq!(rule.start, "2025-06".parse().unwrap());
+        assert_eq!(rule.note.as_deref(), Some("Netflix 2025/06"));
+    }
+
+    #[test]
+    fn notes_match_without_case_digits_or_punctuation() {
+        assert_eq!(normalize_note(Some("Netflix 08/2025")), normalize_note(Some("netflix  09-2025")));
+        assert_eq!(normalize_note(Some("Spotify Premium (Family)")), "spotify premium family");
+        assert_eq!(normalize_note(None), "");
+    }
+}